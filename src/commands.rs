@@ -1,3 +1,22 @@
+pub mod alarms;
+pub mod comm_settings;
+mod common;
+pub mod config;
+pub mod diff;
+pub mod doctor;
+pub mod dump;
+pub mod inputs;
+pub mod lock;
+pub mod log;
+pub mod manual_override;
+pub mod mode;
+pub mod restore;
+pub mod scan;
+pub mod schedule;
+pub mod status;
+pub mod watch;
+pub mod write_file;
+
 pub mod registers {
     use crate::registers::{Mode, Value};
 
@@ -36,6 +55,8 @@ pub mod registers {
         pub minimum: Option<Value>,
         pub maximum: Option<Value>,
         pub description: &'static str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub critical: Option<&'static str>,
     }
 
     impl RegisterSchema {
@@ -44,15 +65,21 @@ pub mod registers {
             use std::iter::zip;
             zip(
                 zip(
-                    zip(zip(zip(zip(ADDRESSES, NAMES), MODES), DATA_TYPES), MINIMUM_VALUES),
-                    MAXIMUM_VALUES,
+                    zip(
+                        zip(zip(zip(zip(ADDRESSES, NAMES), MODES), DATA_TYPES), MINIMUM_VALUES),
+                        MAXIMUM_VALUES,
+                    ),
+                    DESCRIPTIONS,
                 ),
-                DESCRIPTIONS,
+                CRITICAL_EFFECTS,
             )
             .map(
                 |(
-                    (((((&address, &name), &mode), &data_type), &minimum), &maximum),
-                    &description,
+                    (
+                        (((((&address, &name), &mode), &data_type), &minimum), &maximum),
+                        &description,
+                    ),
+                    &critical,
                 )| {
                     RegisterSchema {
                         address,
@@ -63,6 +90,7 @@ pub mod registers {
                         minimum,
                         maximum,
                         description,
+                        critical,
                     }
                 },
             )
@@ -88,6 +116,7 @@ pub mod registers {
                 "Min",
                 "Max",
                 "Description",
+                "Critical",
             ])
             .map_err(Error::WriteOutput)?;
         for register in RegisterSchema::all_registers() {
@@ -109,6 +138,7 @@ pub mod registers {
                             register.minimum.map(|v| v.to_string()).unwrap_or_default(),
                             register.maximum.map(|v| v.to_string()).unwrap_or_default(),
                             register.description.to_string(),
+                            register.critical.unwrap_or_default().to_string(),
                         ]
                     },
                     || register,
@@ -121,12 +151,9 @@ pub mod registers {
 
 pub mod read {
     use crate::connection::{self, Connection};
-    use crate::modbus::{Operation, ResponseKind};
+    use crate::modbus_device_cache::{ReadOptions, ReadOutcome, RegisterBitmask};
     use crate::output;
     use crate::registers::{DataType, RegisterIndex};
-    use futures::{StreamExt as _, TryStreamExt};
-    use std::collections::VecDeque;
-    use std::fmt::Write as _;
     use std::future::Future;
     use std::num::ParseIntError;
 
@@ -139,14 +166,54 @@ pub mod read {
     }
 
     /// Read the value stored in the specified register.
+    ///
+    /// Registers are specified by their name, address or an end-exclusive `START..END` address
+    /// range. A glob such as `'SENSOR_*'` or a regular expression enclosed in slashes such as
+    /// `'/^FAN_LEVEL_.*_RPM$/'` selects all the registers with a matching name.
     #[derive(clap::Parser)]
     pub struct Args {
         #[arg(required = true)]
         pub(super) registers: Vec<String>,
         #[clap(flatten)]
+        pub(super) requests: RequestArgs,
+        #[clap(flatten)]
         pub(super) connection: connection::Args,
         #[clap(flatten)]
-        pub(super) output: output::Args,
+        pub(super) output: output::SampleArgs,
+    }
+
+    #[derive(clap::Args, Clone)]
+    #[group(id = "read::RequestArgs")]
+    pub struct RequestArgs {
+        /// The number of read requests to keep in flight at the same time.
+        #[arg(long, default_value_t = ReadOptions::DEFAULT.pipeline_depth, value_parser = clap::value_parser!(u16).range(1..))]
+        pipeline_depth: u16,
+        /// Give up on a single attempt to read a register after this amount of time.
+        ///
+        /// Replaces the `--read-timeout` of the connection for these reads.
+        #[arg(long)]
+        register_timeout: Option<humantime::Duration>,
+        /// Report a register as failed after retrying the read this many times.
+        #[arg(long, default_value_t = ReadOptions::DEFAULT.max_retries)]
+        max_retries: u32,
+    }
+
+    impl Default for RequestArgs {
+        fn default() -> Self {
+            let ReadOptions { pipeline_depth, register_timeout, max_retries } =
+                ReadOptions::DEFAULT;
+            Self { pipeline_depth, register_timeout: register_timeout.map(Into::into), max_retries }
+        }
+    }
+
+    impl RequestArgs {
+        pub(crate) fn options(&self) -> ReadOptions {
+            ReadOptions {
+                pipeline_depth: self.pipeline_depth,
+                register_timeout: self.register_timeout.map(Into::into),
+                max_retries: self.max_retries,
+            }
+        }
     }
 
     #[derive(thiserror::Error, Debug)]
@@ -163,6 +230,10 @@ pub mod read {
         RegisterRangeEmpty(u16, u16),
         #[error("register `{0}` does not match any known register")]
         RegisterNotFound(String),
+        #[error("register pattern `{1}` is not a valid regular expression")]
+        RegisterPattern(#[source] regex::Error, String),
+        #[error("register pattern `{0}` does not match any known register")]
+        RegisterPatternNoMatch(String),
         #[error("communication with the device failed")]
         Communicate(#[source] crate::connection::Error),
         #[error(transparent)]
@@ -171,10 +242,19 @@ pub mod read {
         WriteOutput(crate::output::Error),
         #[error(transparent)]
         CommitOutput(crate::output::Error),
+        #[error("{0} of the registers could not be read")]
+        Incomplete(usize),
     }
 
     #[derive(serde::Serialize)]
     struct OutputSchema {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        timestamp: Option<String>,
+        device_id: u8,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        transaction_id: Option<u16>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        latency_ms: Option<f64>,
         address: u16,
         #[serde(skip_serializing_if = "Option::is_none")]
         name: Option<&'static str>,
@@ -182,33 +262,105 @@ pub mod read {
         values: Option<Vec<crate::registers::Value>>,
         #[serde(skip_serializing_if = "Option::is_none")]
         exception: Option<u8>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
     }
 
-    enum ReadRequest {
-        SingleRegister { address: u16, index: Option<RegisterIndex> },
-        RegisterRange { address_start: u16, address_end: u16 },
+    /// A register requested to be read.
+    #[derive(Clone, Copy)]
+    struct Requested {
+        address: u16,
+        index: Option<RegisterIndex>,
     }
 
-    impl ReadRequest {
-        fn to_operation(&self) -> Operation {
-            match self {
-                ReadRequest::SingleRegister { address, index: _ } => {
-                    Operation::GetHoldings { address: *address, count: 1 }
-                }
-                ReadRequest::RegisterRange { address_start, address_end } => {
-                    Operation::GetHoldings {
-                        address: *address_start,
-                        count: address_end.checked_sub(*address_start).expect("no overflow"),
+    impl Requested {
+        fn from_address(address: u16) -> Self {
+            Self { address, index: RegisterIndex::from_address(address) }
+        }
+    }
+
+    fn parse_request(register: &str) -> Result<Vec<Requested>, Error> {
+        if let Ok(address) = register.parse::<u16>() {
+            return Ok(vec![Requested::from_address(address)]);
+        }
+        if let Some((l, r)) = register.split_once("..") {
+            let address_start =
+                l.parse::<u16>().map_err(|e| Error::RegisterRangeStartParse(e, l.to_string()))?;
+            let address_end =
+                r.parse::<u16>().map_err(|e| Error::RegisterRangeEndParse(e, r.to_string()))?;
+            if address_end <= address_start {
+                return Err(Error::RegisterRangeEmpty(address_start, address_end));
+            }
+            return Ok((address_start..address_end).map(Requested::from_address).collect());
+        }
+        if let Some(i) = RegisterIndex::from_name(register) {
+            return Ok(vec![Requested { address: i.address(), index: Some(i) }]);
+        }
+
+        Err(Error::RegisterNotFound(register.to_string()))
+    }
+
+    /// Parse a glob (`SENSOR_*`) or a regular expression enclosed in slashes (`/^SENSOR_/`).
+    ///
+    /// Returns `None` if the specification is neither.
+    fn parse_pattern(register: &str) -> Option<Result<regex::Regex, Error>> {
+        let pattern =
+            if let Some(regex) = register.strip_prefix('/').and_then(|r| r.strip_suffix('/')) {
+                regex.to_string()
+            } else if register.contains(['*', '?']) {
+                let mut pattern = String::from("^");
+                for c in register.chars() {
+                    match c {
+                        '*' => pattern.push_str(".*"),
+                        '?' => pattern.push('.'),
+                        c => pattern.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
                     }
                 }
+                pattern.push('$');
+                pattern
+            } else {
+                return None;
+            };
+        Some(regex::Regex::new(&pattern).map_err(|e| Error::RegisterPattern(e, register.into())))
+    }
+
+    fn pattern_requests(register: &str, pattern: &regex::Regex) -> Result<Vec<Requested>, Error> {
+        let matches = RegisterIndex::all()
+            .filter(|r| pattern.is_match(r.name()))
+            .map(|r| Requested { address: r.address(), index: Some(r) })
+            .collect::<Vec<_>>();
+        if matches.is_empty() {
+            return Err(Error::RegisterPatternNoMatch(register.to_string()));
+        }
+        Ok(matches)
+    }
+
+    /// Parse the registers to read, expanding the patterns among them.
+    fn parse_requests(registers: &[String]) -> Result<Vec<Requested>, Error> {
+        let mut requested = vec![];
+        for register in registers {
+            if let Some(pattern) = parse_pattern(register) {
+                requested.extend(pattern_requests(register, &pattern?)?);
+                continue;
             }
+            requested.extend(parse_request(register)?);
         }
+        Ok(requested)
+    }
+
+    /// Parse the registers the same way `read` does, collecting their addresses into a mask.
+    pub(crate) fn parse_mask(registers: &[String]) -> Result<RegisterBitmask, Error> {
+        let mut mask = RegisterBitmask::new();
+        for register in parse_requests(registers)? {
+            mask.set(register.address);
+        }
+        Ok(mask)
     }
 
     #[tokio::main(flavor = "current_thread")]
     pub async fn run(args: Args) -> Result<(), Error> {
-        let Args { registers, connection, output } = args;
-        run_with_connection(&registers, output, async move {
+        let Args { registers, requests, connection, output } = args;
+        run_with_connection(&registers, &requests, output, async move {
             Connection::new(connection).await.map_err(Error::Communicate)
         })
         .await
@@ -216,127 +368,207 @@ pub mod read {
 
     pub async fn run_with_connection(
         registers: &[String],
-        output: output::Args,
+        requests: &RequestArgs,
+        output: output::SampleArgs,
         connection: impl Future<Output = Result<Connection, Error>>,
     ) -> Result<(), Error> {
-        let register_indices = registers
-            .iter()
-            .map(|register| {
-                if let Ok(address) = register.parse::<u16>() {
-                    let index = RegisterIndex::from_address(address);
-                    return Ok(ReadRequest::SingleRegister { address, index });
-                }
-                if let Some((l, r)) = register.split_once("..") {
-                    let address_start = l
-                        .parse::<u16>()
-                        .map_err(|e| Error::RegisterRangeStartParse(e, l.to_string()))?;
-                    let address_end = r
-                        .parse::<u16>()
-                        .map_err(|e| Error::RegisterRangeEndParse(e, r.to_string()))?;
-                    if address_end <= address_start {
-                        return Err(Error::RegisterRangeEmpty(address_start, address_end));
-                    }
-                    return Ok(ReadRequest::RegisterRange { address_start, address_end });
-                }
-                if let Some(i) = RegisterIndex::from_name(register) {
-                    return Ok(ReadRequest::SingleRegister {
-                        address: i.address(),
-                        index: Some(i),
-                    });
-                }
-
-                Err(Error::RegisterNotFound(register.clone()))
-            })
-            .collect::<Result<VecDeque<_>, _>>()?;
+        let requested = parse_requests(registers)?;
+        let mut mask = RegisterBitmask::new();
+        for register in &requested {
+            mask.set(register.address);
+        }
         let mut output = output.to_output().map_err(Error::CreateOutput)?;
-        let heads = vec!["Tx ID", "Address", "Name", "Response"];
+        let heads =
+            vec!["Timestamp", "Device", "Tx ID", "Address", "Name", "Response", "Latency (ms)"];
         output.table_headers(heads).map_err(Error::WriteOutput)?;
         let connection = connection.await?;
-        let mut stream = futures::stream::iter(register_indices.iter())
-            .map(|read_request| {
-                let connection = &connection;
-                Ok::<_, Error>(async move {
-                    let outcome = connection
-                        .send_retrying(read_request.to_operation())
-                        .await
-                        .map_err(Error::Communicate)?;
-                    Ok::<_, Error>((read_request, outcome))
-                })
-            })
-            .try_buffered(2);
-        while let Some(response) = stream.next().await {
-            let (read_request, response) = &response?;
-            let responses = match read_request {
-                ReadRequest::SingleRegister { address, index } => vec![(*address, *index, 0)],
-                ReadRequest::RegisterRange { address_start, address_end } => (*address_start
-                    ..*address_end)
-                    .zip((0..).step_by(2))
-                    .map(|(address, value_offset)| {
-                        (address, RegisterIndex::from_address(address), value_offset)
-                    })
-                    .collect(),
+        let outcomes =
+            crate::modbus_device_cache::read_outcomes(&connection, &mask, &requests.options())
+                .await
+                .map_err(Error::Communicate)?;
+        let device_id = connection.device_id();
+        let no_response = format!("no response after {} attempts", requests.max_retries + 1);
+        let mut failed = 0;
+        for Requested { address, index } in requested {
+            let name = index.map(|r| r.name());
+            let dt = index.map(|r| r.data_type()).unwrap_or(DataType::U16);
+            let outcome = &outcomes[&address];
+            let received = match outcome {
+                ReadOutcome::Value { received, .. } | ReadOutcome::Exception { received, .. } => {
+                    Some(received)
+                }
+                ReadOutcome::NoResponse => {
+                    failed += 1;
+                    None
+                }
             };
-            for (address, register_index, value_offset) in responses {
-                output
-                    .result(
-                        || {
-                            let (dt, name) = register_index
-                                .map(|r| (r.data_type(), r.name()))
-                                .unwrap_or((DataType::U16, "???"));
-                            let value = match &response.kind {
-                                ResponseKind::ErrorCode(c) => format!("server exception {c}"),
-                                ResponseKind::GetHoldings { values } => {
-                                    let mut buf = String::new();
-                                    let range = value_offset..(value_offset + dt.bytes());
-                                    for value in dt.from_bytes(&values[range]) {
-                                        buf.write_fmt(format_args!("{} ", value)).unwrap();
-                                    }
-                                    buf
-                                }
-                                ResponseKind::SetHoldings { .. } => {
-                                    "SetHolding response?".to_string()
-                                }
-                            };
-                            vec![
-                                response.transaction_id.to_string(),
-                                address.to_string(),
-                                name.to_string(),
-                                value,
-                            ]
-                        },
-                        || {
-                            let name = register_index.map(|r| r.name());
-                            let dt = register_index.map(|r| r.data_type()).unwrap_or(DataType::U16);
-                            let (values, exception) = match &response.kind {
-                                ResponseKind::ErrorCode(e) => (None, Some(*e)),
-                                ResponseKind::GetHoldings { values } => {
-                                    let range = value_offset..(value_offset + dt.bytes());
-                                    (Some(dt.from_bytes(&values[range]).collect()), None)
-                                }
-                                ResponseKind::SetHoldings { .. } => (None, None),
-                            };
-                            OutputSchema { address, name, values, exception }
-                        },
-                    )
-                    .map_err(Error::WriteOutput)?;
+            let latency_ms = received.map(|r| r.latency.as_micros() as f64 / 1000.0);
+            output
+                .sample(
+                    || {
+                        let value = match outcome {
+                            ReadOutcome::Value { word, .. } => dt.from_word(*word).to_string(),
+                            ReadOutcome::Exception { code, .. } => {
+                                format!("server exception {code}")
+                            }
+                            ReadOutcome::NoResponse => no_response.clone(),
+                        };
+                        vec![
+                            received.map(|r| r.timestamp.to_string()).unwrap_or_default(),
+                            device_id.to_string(),
+                            received.map(|r| r.transaction_id.to_string()).unwrap_or_default(),
+                            address.to_string(),
+                            name.unwrap_or("???").to_string(),
+                            value,
+                            latency_ms.map(|l| format!("{l:.1}")).unwrap_or_default(),
+                        ]
+                    },
+                    || {
+                        let (values, exception, error) = match outcome {
+                            ReadOutcome::Value { word, .. } => {
+                                (Some(vec![dt.from_word(*word)]), None, None)
+                            }
+                            ReadOutcome::Exception { code, .. } => (None, Some(*code), None),
+                            ReadOutcome::NoResponse => (None, None, Some(no_response.clone())),
+                        };
+                        OutputSchema {
+                            timestamp: received.map(|r| r.timestamp.to_string()),
+                            device_id,
+                            transaction_id: received.map(|r| r.transaction_id),
+                            latency_ms,
+                            address,
+                            name,
+                            values,
+                            exception,
+                            error,
+                        }
+                    },
+                    || {
+                        let ReadOutcome::Value { received, word } = outcome else { return None };
+                        Some(output::Sample {
+                            name: name?,
+                            address,
+                            value: dt.from_word(*word).into(),
+                            device_id: Some(device_id),
+                            timestamp: Some(received.timestamp),
+                        })
+                    },
+                )
+                .map_err(Error::WriteOutput)?;
+        }
+        output.commit().map_err(Error::CommitOutput)?;
+        if failed > 0 {
+            return Err(Error::Incomplete(failed));
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::modbus_device_cache::SetBitsIterator;
+
+        fn addresses(registers: &[&str]) -> Result<Vec<u16>, Error> {
+            let registers = registers.iter().map(|r| r.to_string()).collect::<Vec<_>>();
+            Ok(SetBitsIterator::new(&parse_mask(&registers)?).collect())
+        }
+
+        #[test]
+        fn masks() {
+            let pband = RegisterIndex::from_name("DEMC_RH_SETTINGS_PBAND").unwrap().address();
+            let selected = addresses(&["12", "DEMC_RH_SETTINGS_PBAND", "20..23", "21"]).unwrap();
+            assert_eq!(selected, [12, 20, 21, 22, pband]);
+            assert!(matches!(addresses(&["20..20"]), Err(Error::RegisterRangeEmpty(20, 20))));
+            assert!(matches!(addresses(&["20..x"]), Err(Error::RegisterRangeEndParse(..))));
+            assert!(matches!(addresses(&["NOT_A_REGISTER"]), Err(Error::RegisterNotFound(_))));
+            let selected = addresses(&["FAN_LEVEL_SAF_M??_RPM"]).unwrap();
+            let min = RegisterIndex::from_name("FAN_LEVEL_SAF_MIN_RPM").unwrap().address();
+            let max = RegisterIndex::from_name("FAN_LEVEL_SAF_MAX_RPM").unwrap().address();
+            assert_eq!(selected, [min, max]);
+            assert!(matches!(addresses(&["NO_SUCH_*"]), Err(Error::RegisterPatternNoMatch(_))));
+        }
+
+        fn pattern(register: &str) -> regex::Regex {
+            parse_pattern(register).expect("is a pattern").expect("is valid")
+        }
+
+        #[test]
+        fn globs() {
+            let glob = pattern("SENSOR_*");
+            assert!(glob.is_match("SENSOR_OAT") && glob.is_match("SENSOR_"));
+            assert!(!glob.is_match("X_SENSOR_OAT"));
+            let glob = pattern("FAN_LEVEL_?");
+            assert!(glob.is_match("FAN_LEVEL_1") && !glob.is_match("FAN_LEVEL_10"));
+            // Other regular expression syntax is matched literally.
+            assert!(pattern("A.B*").is_match("A.BC") && !pattern("A.B*").is_match("AXBC"));
+        }
+
+        #[test]
+        fn regular_expressions() {
+            let regex = pattern("/^FAN_LEVEL_.*_RPM$/");
+            assert!(regex.is_match("FAN_LEVEL_SAF_MIN_RPM") && !regex.is_match("FAN_LEVEL_SAF"));
+            // Unlike globs, regular expressions are not anchored.
+            assert!(pattern("/RPM/").is_match("FAN_LEVEL_SAF_MIN_RPM"));
+            assert!(matches!(parse_pattern("/(/"), Some(Err(Error::RegisterPattern(..)))));
+        }
+
+        #[test]
+        fn other_registers_are_not_patterns() {
+            for register in ["SENSOR_OAT", "1101", "1101..1104", "/SENSOR"] {
+                assert!(parse_pattern(register).is_none(), "{register}");
             }
         }
-        output.commit().map_err(Error::CommitOutput)
     }
 }
 
 pub mod write {
+    use super::write_file;
     use crate::connection::{self, Connection};
     use crate::modbus::{Operation, Response, ResponseKind};
-    use crate::registers::{ParseValueError, RegisterIndex};
+    use crate::modbus_device_cache::{ModbusDeviceValues, RegisterBitmask};
+    use crate::registers::{ParseValueError, RegisterIndex, Value};
+    use std::path::PathBuf;
 
     /// Write the values into specified registers.
+    ///
+    /// Several consecutive registers can be written at once with `START..END=VAL,VAL,…` or
+    /// `REG=VAL,VAL,…`, where the values go into the registers starting at `REG`.
     #[derive(clap::Parser)]
     pub struct Args {
-        #[arg(required = true)]
+        #[arg(required_unless_present_any = ["from", "undo"])]
         registers: Vec<String>,
+        /// Write the register values listed in this CSV, JSON or TOML file instead of the ones
+        /// given on the command line.
+        #[arg(long, value_name = "FILE", conflicts_with = "registers")]
+        from: Option<PathBuf>,
+        /// The format of the `--from` file. Inferred from the file extension by default.
+        #[arg(long, value_enum, requires = "from")]
+        from_format: Option<write_file::Format>,
+        /// Restore the values changed by the last N operations recorded in the journal.
+        #[arg(
+            long,
+            value_name = "N",
+            num_args = 0..=1,
+            default_missing_value = "1",
+            conflicts_with_all = ["registers", "from"]
+        )]
+        undo: Option<usize>,
         #[arg(long)]
         no_read_back: bool,
+        /// Only show the current and the new values, do not write anything.
+        #[arg(long, conflicts_with = "confirm")]
+        dry_run: bool,
+        /// Show the current and the new values, and ask for confirmation before writing.
+        #[arg(long)]
+        confirm: bool,
+        /// Unlock changes to the settings with this administrator password before writing, and
+        /// lock them again afterwards.
+        #[arg(long, value_name = "PIN")]
+        unlock_with: Option<super::lock::Pin>,
+        /// Write registers that are read-only or safety-critical, and values outside of the
+        /// documented range.
+        #[arg(long)]
+        force: bool,
         #[clap(flatten)]
         output: crate::output::Args,
         #[clap(flatten)]
@@ -347,89 +579,474 @@ pub mod write {
     pub enum Error {
         #[error("could not read back the written registers")]
         Readback(#[source] super::read::Error),
+        #[error("could not load the register values to write")]
+        Load(#[source] write_file::Error),
         #[error(
             "could not parse {0} into a register description and value to write (expected format `REG=VAL`)"
         )]
         RegisterMalformed(String),
         #[error("register address {0} is not known")]
         RegisterAddressUnknown(u16),
+        #[error("register range `{0}` could not be parsed (expected format `START..END`)")]
+        RegisterRangeMalformed(String),
+        #[error("{0} covers {1} registers, but {2} values were given")]
+        ValueCountMismatch(String, usize, usize),
         #[error("register {0} is not known")]
         RegisterNotFound(String),
         #[error("could not parse value {0} for register {1}")]
         ParseValue(String, String, #[source] ParseValueError),
+        #[error("register {0} is read-only, use `--force` to try writing it anyway")]
+        NotWritable(&'static str),
+        #[error(
+            "value {1} for register {0} is outside of the range {2}, use `--force` to write it anyway"
+        )]
+        OutOfRange(&'static str, Value, String),
+        #[error("writing {0} {1}, use `--force` if this is intended")]
+        Critical(&'static str, &'static str),
         #[error("communication with the device failed")]
         Communicate(#[source] crate::connection::Error),
+        #[error("could not unlock the settings")]
+        Unlock(#[source] super::lock::Error),
+        #[error("could not lock the settings again")]
+        Relock(#[source] super::lock::Error),
+        #[error("{0} registers could not be written")]
+        Incomplete(usize),
+        #[error("could not read the confirmation")]
+        ReadConfirmation(#[source] std::io::Error),
+        #[error("could not use the journal")]
+        Journal(#[source] crate::journal::Error),
+        #[error("write was not confirmed, nothing was changed")]
+        NotConfirmed,
+        #[error(transparent)]
+        CreateOutput(crate::output::Error),
+        #[error(transparent)]
+        WriteOutput(crate::output::Error),
+        #[error(transparent)]
+        CommitOutput(crate::output::Error),
+    }
+
+    /// The maximum number of registers a single `SetHoldings` request can carry.
+    const MAX_WRITE_REGISTERS: usize = 123;
+
+    #[derive(serde::Serialize, Clone, Copy, PartialEq, Eq, strum::Display)]
+    #[serde(rename_all = "kebab-case")]
+    #[strum(serialize_all = "kebab-case")]
+    enum Outcome {
+        Written,
+        Exception,
+        NoResponse,
+    }
+
+    #[derive(serde::Serialize)]
+    struct OutputSchema {
+        address: u16,
+        name: &'static str,
+        value: Value,
+        outcome: Outcome,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        exception: Option<u8>,
+    }
+
+    #[derive(serde::Serialize)]
+    struct PlanSchema {
+        address: u16,
+        name: &'static str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        old: Option<Value>,
+        new: Value,
+        description: &'static str,
+    }
+
+    /// Resolve a register given by its address or name.
+    fn resolve_register(register: &str) -> Result<RegisterIndex, Error> {
+        if let Ok(address) = register.parse::<u16>() {
+            RegisterIndex::from_address(address).ok_or(Error::RegisterAddressUnknown(address))
+        } else {
+            RegisterIndex::from_name(register)
+                .ok_or_else(|| Error::RegisterNotFound(register.to_string()))
+        }
+    }
+
+    /// Resolve the consecutive registers receiving `count` values.
+    ///
+    /// These are either given as a `start..end` range of addresses, or start at a single register.
+    fn resolve_registers(register: &str, count: usize) -> Result<Vec<RegisterIndex>, Error> {
+        let addresses = if let Some((l, r)) = register.split_once("..") {
+            let (Ok(start), Ok(end)) = (l.parse::<u16>(), r.parse::<u16>()) else {
+                return Err(Error::RegisterRangeMalformed(register.to_string()));
+            };
+            if usize::from(end.saturating_sub(start)) != count {
+                let covered = end.saturating_sub(start).into();
+                return Err(Error::ValueCountMismatch(register.to_string(), covered, count));
+            }
+            start..end
+        } else {
+            let start = resolve_register(register)?.address();
+            let end = u16::try_from(count)
+                .ok()
+                .and_then(|count| start.checked_add(count))
+                .ok_or(Error::RegisterAddressUnknown(u16::MAX))?;
+            start..end
+        };
+        addresses
+            .map(|address| {
+                RegisterIndex::from_address(address).ok_or(Error::RegisterAddressUnknown(address))
+            })
+            .collect()
+    }
+
+    /// Parse and validate the value to write into the register.
+    fn parse_value(
+        register_index: RegisterIndex,
+        value: &str,
+        force: bool,
+    ) -> Result<Value, Error> {
+        let name = register_index.name();
+        if !register_index.mode().is_writable() {
+            if !force {
+                return Err(Error::NotWritable(name));
+            }
+            tracing::warn!(register = name, "not writable, will try writing anyway…!")
+        }
+        let value = register_index
+            .data_type()
+            .parse_string(value)
+            .map_err(|e| Error::ParseValue(value.into(), name.into(), e))?;
+        check_value(register_index, value, force)?;
+        Ok(value)
+    }
+
+    /// Refuse out-of-range values and safety-critical registers unless `force` is set.
+    fn check_value(register: RegisterIndex, value: Value, force: bool) -> Result<(), Error> {
+        let name = register.name();
+        if let Err(range) = super::common::check_range(register, value) {
+            if !force {
+                return Err(Error::OutOfRange(name, value, range));
+            }
+            tracing::warn!(register = name, %value, "out of range, will write anyway…!")
+        }
+        let effect =
+            super::common::check_critical(register, force).map_err(|e| Error::Critical(name, e))?;
+        if let Some(effect) = effect {
+            tracing::warn!(register = name, %value, effect, "safety-critical, will write anyway…!")
+        }
+        Ok(())
     }
 
     #[tokio::main(flavor = "current_thread")]
     pub async fn run(args: Args) -> Result<(), Error> {
         let mut readback_registers = vec![];
         let mut write_ops = vec![];
+        if let Some(path) = &args.from {
+            let format = args.from_format.unwrap_or_else(|| write_file::Format::from_path(path));
+            for entry in write_file::load(path, format).map_err(Error::Load)? {
+                let register = resolve_register(&entry.register)?;
+                write_ops.push((register, parse_value(register, &entry.value, args.force)?));
+            }
+        }
         for register in args.registers {
             let Some((register, value)) = register.split_once("=") else {
                 return Err(Error::RegisterMalformed(register));
             };
-            readback_registers.push(register.to_string());
-            let register_index = if let Ok(address) = register.parse::<u16>() {
-                let Some(i) = RegisterIndex::from_address(address) else {
-                    return Err(Error::RegisterAddressUnknown(address));
-                };
-                i
-            } else if let Some(i) = RegisterIndex::from_name(register) {
-                i
-            } else {
-                return Err(Error::RegisterNotFound(register.to_string()));
-            };
-            if !register_index.mode().is_writable() {
-                tracing::warn!(register, "not writable, will try writing anyway…!")
+            let values = value.split(',').map(str::trim).collect::<Vec<_>>();
+            for (register, value) in
+                resolve_registers(register, values.len())?.into_iter().zip(values)
+            {
+                readback_registers.push(register.address().to_string());
+                write_ops.push((register, parse_value(register, value, args.force)?));
+            }
+        }
+        let mut expected = vec![];
+        // The index of the undone operation each of the `write_ops` belongs to.
+        let mut undone_operation = vec![];
+        if let Some(count) = args.undo {
+            let operations = crate::journal::last_operations(count).map_err(Error::Journal)?;
+            // Most recent changes come first, so that the oldest previous value is written last.
+            for (index, operation) in operations.iter().enumerate() {
+                for change in operation.changes.iter().rev() {
+                    let register = change.register().map_err(Error::Journal)?;
+                    let before = change.before(register).map_err(Error::Journal)?;
+                    check_value(register, before, args.force)?;
+                    write_ops.push((register, before));
+                    expected.push((register, change.after));
+                    undone_operation.push(index);
+                }
             }
-            let value = register_index
-                .data_type()
-                .parse_string(value)
-                .map_err(|e| Error::ParseValue(value.into(), register.into(), e))?;
-            write_ops.push((register_index, value));
         }
         let connection = Connection::new(args.connection.clone()).await.unwrap();
-        for (register, val) in write_ops {
+        let mut mask = RegisterBitmask::new();
+        for (register, _) in &write_ops {
+            mask.set(register.address());
+        }
+        let mut current = ModbusDeviceValues::new();
+        current.read_registers(&connection, &mask).await.map_err(Error::Communicate)?;
+        for (register, after) in expected {
+            if current.value_of(register).map(crate::snapshot::Number::from) != Some(after) {
+                tracing::warn!(
+                    register = register.name(),
+                    "value has changed since the journaled operation, undoing anyway"
+                );
+            }
+        }
+        if args.dry_run || args.confirm {
+            print_plan(&write_ops, &current, args.output.clone())?;
+            if args.dry_run {
+                return Ok(());
+            }
+            confirm(write_ops.len())?;
+        }
+        if let Some(pin) = args.unlock_with {
+            super::lock::unlock(&connection, pin).await.map_err(Error::Unlock)?;
+        }
+        let written = write_registers(&connection, &write_ops).await;
+        if args.unlock_with.is_some() {
+            let relocked = super::lock::relock(&connection).await;
+            match (&written, relocked) {
+                // Report the relock failure without hiding why the write failed.
+                (Err(_), Err(e)) => tracing::error!(
+                    message = "could not lock the settings again",
+                    error = (&e as &dyn std::error::Error)
+                ),
+                (_, relocked) => relocked.map_err(Error::Relock)?,
+            }
+        }
+        let outcomes = written?;
+        let changes = write_ops.iter().zip(&outcomes).map(|(&(register, value), outcome)| {
+            let before = current.value_of(register)?;
+            (outcome.0 == Outcome::Written).then_some((register, before, value))
+        });
+        match args.undo {
+            Some(count) => {
+                let undone = fully_undone(count, &undone_operation, &outcomes);
+                let (changes, partial): (Vec<_>, Vec<_>) =
+                    changes.zip(&undone_operation).partition(|&(_, &operation)| operation < undone);
+                if undone > 0 {
+                    let changes = changes.into_iter().filter_map(|(change, _)| change);
+                    crate::journal::record_undo(undone, changes).map_err(Error::Journal)?;
+                }
+                // The operations that were only partially undone stay in the journal, but the
+                // values that were restored are changes of their own.
+                let partial = partial.into_iter().filter_map(|(change, _)| change);
+                crate::journal::record("partial undo", partial).map_err(Error::Journal)?;
+            }
+            None => crate::journal::record("write", changes.flatten()).map_err(Error::Journal)?,
+        }
+
+        if args.from.is_some() || args.undo.is_some() {
+            return print_report(&write_ops, &outcomes, args.output);
+        }
+        if !args.no_read_back {
+            let requests = super::read::RequestArgs::default();
+            super::read::run_with_connection(
+                &readback_registers,
+                &requests,
+                args.output.into(),
+                async move { Ok(connection) },
+            )
+            .await
+            .map_err(Error::Readback)?;
+        }
+        Ok(())
+    }
+
+    /// Output the current and the new value of each register that is about to be written.
+    fn print_plan(
+        write_ops: &[(RegisterIndex, Value)],
+        current: &ModbusDeviceValues,
+        output: crate::output::Args,
+    ) -> Result<(), Error> {
+        let mut output = output.to_output().map_err(Error::CreateOutput)?;
+        let heads = vec!["Address", "Name", "Old", "New", "Description"];
+        output.table_headers(heads).map_err(Error::WriteOutput)?;
+        for &(register, new) in write_ops {
+            let old = current.value_of(register);
+            output
+                .result(
+                    || {
+                        vec![
+                            register.address().to_string(),
+                            register.name().to_string(),
+                            old.map(|v| v.to_string()).unwrap_or_default(),
+                            new.to_string(),
+                            register.description().to_string(),
+                        ]
+                    },
+                    || PlanSchema {
+                        address: register.address(),
+                        name: register.name(),
+                        old,
+                        new,
+                        description: register.description(),
+                    },
+                )
+                .map_err(Error::WriteOutput)?;
+        }
+        output.commit().map_err(Error::CommitOutput)
+    }
+
+    /// The number of the most recent operations all of whose values were restored.
+    ///
+    /// Only the most recent operations can be recorded as undone, so an operation that failed to
+    /// be undone keeps the older ones from counting as undone as well.
+    fn fully_undone(
+        count: usize,
+        operations: &[usize],
+        outcomes: &[(Outcome, Option<u8>)],
+    ) -> usize {
+        operations
+            .iter()
+            .zip(outcomes)
+            .filter(|(_, outcome)| outcome.0 != Outcome::Written)
+            .map(|(&operation, _)| operation)
+            .min()
+            .unwrap_or(count)
+    }
+
+    fn confirm(count: usize) -> Result<(), Error> {
+        let question = format!("Write {count} register(s)? [y/N]");
+        if !super::common::confirm(&question, super::common::YES)
+            .map_err(Error::ReadConfirmation)?
+        {
+            return Err(Error::NotConfirmed);
+        }
+        Ok(())
+    }
+
+    fn print_report(
+        write_ops: &[(RegisterIndex, Value)],
+        outcomes: &[(Outcome, Option<u8>)],
+        output: crate::output::Args,
+    ) -> Result<(), Error> {
+        let mut output = output.to_output().map_err(Error::CreateOutput)?;
+        let heads = vec!["Address", "Name", "Value", "Outcome"];
+        output.table_headers(heads).map_err(Error::WriteOutput)?;
+        let mut failures = 0;
+        for (&(register, value), &(outcome, exception)) in write_ops.iter().zip(outcomes) {
+            if outcome != Outcome::Written {
+                failures += 1;
+            }
+            output
+                .result(
+                    || {
+                        let outcome = match exception {
+                            Some(code) => format!("{outcome} {code}"),
+                            None => outcome.to_string(),
+                        };
+                        vec![
+                            register.address().to_string(),
+                            register.name().to_string(),
+                            value.to_string(),
+                            outcome,
+                        ]
+                    },
+                    || OutputSchema {
+                        address: register.address(),
+                        name: register.name(),
+                        value,
+                        outcome,
+                        exception,
+                    },
+                )
+                .map_err(Error::WriteOutput)?;
+        }
+        output.commit().map_err(Error::CommitOutput)?;
+        if failures > 0 {
+            return Err(Error::Incomplete(failures));
+        }
+        Ok(())
+    }
+
+    /// Write the registers in the given order, merging runs of adjacent registers into a single
+    /// request so that related values (such as the clock) are applied together.
+    ///
+    /// Returns the outcome for each of the registers.
+    async fn write_registers(
+        connection: &Connection,
+        write_ops: &[(RegisterIndex, Value)],
+    ) -> Result<Vec<(Outcome, Option<u8>)>, Error> {
+        let mut outcomes = Vec::with_capacity(write_ops.len());
+        let transactions = write_ops
+            .chunk_by(|(a, _), (b, _)| a.address().checked_add(1) == Some(b.address()))
+            .flat_map(|run| run.chunks(MAX_WRITE_REGISTERS));
+        for transaction in transactions {
+            let address = transaction[0].0.address();
             let outcome = connection
                 .send(Operation::SetHoldings {
-                    address: register.address(),
-                    values: vec![val.into_inner()],
+                    address,
+                    values: transaction.iter().map(|(_, v)| v.into_inner()).collect(),
                 })
                 .await
                 .map_err(Error::Communicate)?;
-            let address = register.address();
-            match outcome {
+            let outcome = match outcome {
                 Some(Response { kind: ResponseKind::ErrorCode(c), .. }) => {
                     tracing::warn!(
                         address,
                         exception = c,
                         "device responded with an exception code to a set command"
-                    )
+                    );
+                    (Outcome::Exception, Some(c))
                 }
                 Some(Response {
                     kind: ResponseKind::SetHoldings { address, words: count },
                     ..
                 }) => {
-                    tracing::info!(address, count, "registers set")
+                    tracing::info!(address, count, "registers set");
+                    (Outcome::Written, None)
                 }
                 Some(Response { kind: _, .. }) => {
-                    tracing::warn!(address, "unexpected response to a set command")
+                    tracing::warn!(address, "unexpected response to a set command");
+                    (Outcome::NoResponse, None)
                 }
                 None => {
-                    tracing::warn!(address, "no response to set register command")
+                    tracing::warn!(address, "no response to set register command");
+                    (Outcome::NoResponse, None)
                 }
-            }
+            };
+            outcomes.extend(std::iter::repeat_n(outcome, transaction.len()));
         }
+        Ok(outcomes)
+    }
 
-        if !args.no_read_back {
-            super::read::run_with_connection(&readback_registers, args.output, async move {
-                Ok(connection)
-            })
-            .await
-            .map_err(Error::Readback)?;
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const WRITTEN: (Outcome, Option<u8>) = (Outcome::Written, None);
+        const FAILED: (Outcome, Option<u8>) = (Outcome::NoResponse, None);
+
+        #[test]
+        fn all_operations_undone() {
+            assert_eq!(fully_undone(2, &[0, 0, 1], &[WRITTEN, WRITTEN, WRITTEN]), 2);
+        }
+
+        #[test]
+        fn failure_stops_older_operations_from_being_undone() {
+            assert_eq!(fully_undone(3, &[0, 1, 1, 2], &[WRITTEN, WRITTEN, FAILED, WRITTEN]), 1);
+            assert_eq!(fully_undone(2, &[0, 1], &[FAILED, WRITTEN]), 0);
+        }
+
+        fn addresses(register: &str, count: usize) -> Result<Vec<u16>, Error> {
+            Ok(resolve_registers(register, count)?.into_iter().map(|r| r.address()).collect())
+        }
+
+        #[test]
+        fn consecutive_registers() {
+            assert_eq!(addresses("1101..1104", 3).unwrap(), [1101, 1102, 1103]);
+            assert_eq!(addresses("USERMODE_HOLIDAY_TIME", 2).unwrap(), [1101, 1102]);
+            assert_eq!(addresses("1102", 1).unwrap(), [1102]);
+        }
+
+        #[test]
+        fn invalid_consecutive_registers() {
+            let result = addresses("1101..1104", 2);
+            assert!(matches!(result, Err(Error::ValueCountMismatch(_, 3, 2))));
+            assert!(matches!(addresses("1104..1101", 3), Err(Error::ValueCountMismatch(..))));
+            assert!(matches!(addresses("1101..x", 1), Err(Error::RegisterRangeMalformed(_))));
+            assert!(matches!(addresses("1100..1102", 2), Err(Error::RegisterAddressUnknown(1100))));
+            assert!(matches!(addresses("NO_SUCH_REGISTER", 1), Err(Error::RegisterNotFound(_))));
+            let result = addresses("USERMODE_HOLIDAY_TIME", usize::from(u16::MAX));
+            assert!(matches!(result, Err(Error::RegisterAddressUnknown(u16::MAX))));
         }
-        Ok(())
     }
 }
 
@@ -0,0 +1,344 @@
+//! Inspecting and clearing the device alarms.
+//!
+//! Alarms are referred to by the same names as the properties of the `alarm` node in the MQTT
+//! interface (e.g. `supply-air-fan-control`), or by the name of their state register.
+
+use super::common::{check_critical, register};
+use crate::connection::{self, Connection};
+use crate::homie::alarm_node::{AlarmNode, AlarmValue};
+use crate::homie::node::Node as _;
+use crate::modbus::{Operation, ResponseKind};
+use crate::modbus_device_cache::{ModbusDeviceValues, RegisterBitmask};
+use crate::output;
+use crate::registers::RegisterIndex;
+
+/// Inspect and clear the device alarms.
+#[derive(clap::Parser)]
+pub struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// List the alarms that are currently not clear.
+    List {
+        /// List all alarms, including the clear ones.
+        #[arg(long)]
+        all: bool,
+        #[clap(flatten)]
+        connection: connection::Args,
+        #[clap(flatten)]
+        output: output::Args,
+    },
+    /// Show the events recorded in the alarm log.
+    Log {
+        #[clap(flatten)]
+        connection: connection::Args,
+        #[clap(flatten)]
+        output: output::Args,
+    },
+    /// Clear an alarm, or `all` of the alarms that are currently firing.
+    Clear {
+        alarm: String,
+        /// Clear safety-critical alarms as well, such as the fire alarm.
+        #[arg(long)]
+        force: bool,
+        #[clap(flatten)]
+        connection: connection::Args,
+        #[clap(flatten)]
+        output: output::Args,
+    },
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("`{0}` does not match any known alarm")]
+    AlarmNotFound(String),
+    #[error("could establish client connection with the device")]
+    EstablishClient(#[source] crate::connection::Error),
+    #[error("communication with the device failed")]
+    Communicate(#[source] crate::connection::Error),
+    #[error("clearing the {0} alarm {1}, use `--force` if this is intended")]
+    Critical(&'static str, &'static str),
+    #[error("device rejected clearing the {0} alarm with exception {1}")]
+    Rejected(&'static str, u8),
+    #[error(transparent)]
+    CreateOutput(crate::output::Error),
+    #[error(transparent)]
+    WriteOutput(crate::output::Error),
+    #[error(transparent)]
+    CommitOutput(crate::output::Error),
+}
+
+/// The number of entries in the alarm log.
+const LOG_ENTRIES: u16 = 20;
+/// The number of registers describing a single alarm log entry.
+const LOG_ENTRY_REGISTERS: u16 = 10;
+
+struct Alarm {
+    name: &'static str,
+    /// The register holding the alarm state.
+    register: RegisterIndex,
+    /// The register to write to clear the alarm.
+    clear: RegisterIndex,
+}
+
+/// Find the register clearing the alarm, `ALARM_X_CLEAR_ALARM` for the `ALARM_X_ALARM` state.
+fn clear_register(register: RegisterIndex) -> Option<RegisterIndex> {
+    let alarm = register.name().strip_suffix("_ALARM")?;
+    RegisterIndex::from_name(&format!("{alarm}_CLEAR_ALARM"))
+}
+
+fn alarms() -> impl Iterator<Item = Alarm> {
+    AlarmNode::new().properties().iter().filter_map(|property| {
+        let &[register] = property.kind.registers() else { return None };
+        if !register.name().starts_with("ALARM_") {
+            return None;
+        }
+        let clear = clear_register(register)?;
+        Some(Alarm { name: property.prop_id.as_str(), register, clear })
+    })
+}
+
+/// Describe an alarm state register value the same way as `AlarmValue` does.
+fn state_name(state: u16) -> String {
+    match AlarmValue::from_repr(state) {
+        Some(value) => <&'static str>::from(value).to_string(),
+        None => state.to_string(),
+    }
+}
+
+#[derive(serde::Serialize)]
+struct AlarmSchema {
+    alarm: &'static str,
+    register: &'static str,
+    state: String,
+}
+
+#[derive(serde::Serialize)]
+struct LogSchema {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time: Option<String>,
+    id: u16,
+    previous: String,
+    now: String,
+    code: u16,
+}
+
+/// A single alarm log event.
+#[derive(Debug, PartialEq)]
+struct LogEntry {
+    /// Absent if the device recorded a date or time that does not exist.
+    time: Option<jiff::civil::DateTime>,
+    /// The alarm identifier as recorded by the device.
+    id: u16,
+    now: u16,
+    previous: u16,
+    code: u16,
+}
+
+impl LogEntry {
+    /// Decode the registers of an entry, `ALARM_LOG_N_ID` through `ALARM_LOG_N_CODE`.
+    ///
+    /// Returns `None` for entries that have not been used yet.
+    fn decode(words: [u16; LOG_ENTRY_REGISTERS as usize]) -> Option<Self> {
+        if words.iter().all(|w| *w == 0) {
+            return None;
+        }
+        let [id, now, previous, year, month, day, hour, minute, second, code] = words;
+        let time = (|| {
+            let date = jiff::civil::Date::new(
+                year.try_into().ok()?,
+                month.try_into().ok()?,
+                day.try_into().ok()?,
+            );
+            let time = jiff::civil::Time::new(
+                hour.try_into().ok()?,
+                minute.try_into().ok()?,
+                second.try_into().ok()?,
+                0,
+            );
+            Some(date.ok()?.to_datetime(time.ok()?))
+        })();
+        Some(Self { time, id, now, previous, code })
+    }
+}
+
+async fn read_states(connection: &Connection) -> Result<ModbusDeviceValues, Error> {
+    let mut mask = RegisterBitmask::new();
+    for alarm in alarms() {
+        mask.set(alarm.register.address());
+    }
+    let mut values = ModbusDeviceValues::new();
+    values.read_registers(connection, &mask).await.map_err(Error::Communicate)?;
+    Ok(values)
+}
+
+fn print_states(
+    alarms: &[Alarm],
+    values: &ModbusDeviceValues,
+    all: bool,
+    output: output::Args,
+) -> Result<(), Error> {
+    let mut output = output.to_output().map_err(Error::CreateOutput)?;
+    output.table_headers(vec!["Alarm", "Register", "State"]).map_err(Error::WriteOutput)?;
+    for alarm in alarms {
+        let Some(state) = values.value_of(alarm.register).map(|v| v.into_inner()) else {
+            tracing::warn!(alarm = alarm.name, "could not read the alarm state");
+            continue;
+        };
+        if !all && AlarmValue::from_repr(state) == Some(AlarmValue::Clear) {
+            continue;
+        }
+        let state = state_name(state);
+        output
+            .result(
+                || vec![alarm.name.to_string(), alarm.register.name().to_string(), state.clone()],
+                || AlarmSchema {
+                    alarm: alarm.name,
+                    register: alarm.register.name(),
+                    state: state.clone(),
+                },
+            )
+            .map_err(Error::WriteOutput)?;
+    }
+    output.commit().map_err(Error::CommitOutput)
+}
+
+async fn print_log(connection: &Connection, output: output::Args) -> Result<(), Error> {
+    let first = register("ALARM_LOG_1_ID").address();
+    let mut mask = RegisterBitmask::new();
+    for address in first..first + LOG_ENTRIES * LOG_ENTRY_REGISTERS {
+        mask.set(address);
+    }
+    let mut values = ModbusDeviceValues::new();
+    values.read_registers(connection, &mask).await.map_err(Error::Communicate)?;
+    let mut output = output.to_output().map_err(Error::CreateOutput)?;
+    let heads = vec!["Time", "ID", "Previous", "Now", "Code"];
+    output.table_headers(heads).map_err(Error::WriteOutput)?;
+    for entry in 0..LOG_ENTRIES {
+        let start = first + entry * LOG_ENTRY_REGISTERS;
+        let words = (start..start + LOG_ENTRY_REGISTERS)
+            .map(|address| values.value_of_address(address))
+            .collect::<Option<Vec<_>>>();
+        let Some(words) = words.and_then(|words| words.try_into().ok()) else {
+            tracing::warn!(entry = entry + 1, "could not read the alarm log entry");
+            continue;
+        };
+        let Some(LogEntry { time, id, now, previous, code }) = LogEntry::decode(words) else {
+            continue;
+        };
+        let time = time.map(|time| time.to_string());
+        let (previous, now) = (state_name(previous), state_name(now));
+        output
+            .result(
+                || {
+                    vec![
+                        time.clone().unwrap_or_default(),
+                        id.to_string(),
+                        previous.clone(),
+                        now.clone(),
+                        code.to_string(),
+                    ]
+                },
+                || LogSchema {
+                    time: time.clone(),
+                    id,
+                    previous: previous.clone(),
+                    now: now.clone(),
+                    code,
+                },
+            )
+            .map_err(Error::WriteOutput)?;
+    }
+    output.commit().map_err(Error::CommitOutput)
+}
+
+#[tokio::main(flavor = "current_thread")]
+pub async fn run(args: Args) -> Result<(), Error> {
+    match args.command {
+        Command::List { all, connection, output } => {
+            let connection = Connection::new(connection).await.map_err(Error::EstablishClient)?;
+            let values = read_states(&connection).await?;
+            print_states(&alarms().collect::<Vec<_>>(), &values, all, output)
+        }
+        Command::Log { connection, output } => {
+            let connection = Connection::new(connection).await.map_err(Error::EstablishClient)?;
+            print_log(&connection, output).await
+        }
+        Command::Clear { alarm, force, connection, output } => {
+            let selected = if alarm == "all" {
+                None
+            } else {
+                let name = alarm.to_lowercase();
+                let found = alarms()
+                    .find(|a| a.name == name || a.register.name() == alarm.to_uppercase())
+                    .ok_or_else(|| Error::AlarmNotFound(alarm.clone()))?;
+                Some(found)
+            };
+            let connection = Connection::new(connection).await.map_err(Error::EstablishClient)?;
+            let values = read_states(&connection).await?;
+            let to_clear = match selected {
+                Some(alarm) => vec![alarm],
+                None => alarms()
+                    .filter(|a| {
+                        let state = values.value_of(a.register).map(|v| v.into_inner());
+                        state.and_then(AlarmValue::from_repr) == Some(AlarmValue::Firing)
+                    })
+                    .collect(),
+            };
+            for alarm in &to_clear {
+                let effect = check_critical(alarm.clear, force)
+                    .map_err(|effect| Error::Critical(alarm.name, effect))?;
+                if let Some(effect) = effect {
+                    tracing::warn!(
+                        alarm = alarm.name,
+                        effect,
+                        "safety-critical, will clear anyway…!"
+                    );
+                }
+            }
+            for alarm in &to_clear {
+                let address = alarm.clear.address();
+                tracing::info!(alarm = alarm.name, "clearing");
+                let operation = Operation::SetHoldings { address, values: vec![1] };
+                let response =
+                    connection.send_retrying(operation).await.map_err(Error::Communicate)?;
+                if let ResponseKind::ErrorCode(code) = response.kind {
+                    return Err(Error::Rejected(alarm.name, code));
+                }
+            }
+            let values = read_states(&connection).await?;
+            print_states(&to_clear, &values, true, output)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clear_registers() {
+        let states = AlarmNode::new()
+            .properties()
+            .iter()
+            .filter(|p| matches!(p.kind.registers(), [r] if r.name().starts_with("ALARM_")))
+            .count();
+        assert_eq!(alarms().count(), states, "every alarm can be cleared");
+        let fire = alarms().find(|a| a.register.name() == "ALARM_FIRE_ALARM_ALARM").unwrap();
+        assert_eq!(fire.clear.name(), "ALARM_FIRE_ALARM_CLEAR_ALARM");
+    }
+
+    #[test]
+    fn log_entries() {
+        assert_eq!(LogEntry::decode([0; 10]), None);
+        let entry = LogEntry::decode([14, 3, 0, 2024, 2, 29, 13, 5, 9, 1]).unwrap();
+        let time = jiff::civil::date(2024, 2, 29).at(13, 5, 9, 0);
+        assert_eq!(entry, LogEntry { time: Some(time), id: 14, now: 3, previous: 0, code: 1 });
+        // Not a leap year.
+        let entry = LogEntry::decode([14, 3, 0, 2023, 2, 29, 13, 5, 9, 1]).unwrap();
+        assert_eq!(entry.time, None);
+    }
+}
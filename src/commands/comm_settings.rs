@@ -0,0 +1,326 @@
+//! Inspecting and changing the Modbus communication settings of the device.
+//!
+//! The device stops responding to the current session as soon as any of these settings change,
+//! so after applying them a new connection is made with the new device ID and baudrate to confirm
+//! that the device is still reachable.
+
+use super::common::{YES, check_critical, register};
+use crate::connection::{self, Connection};
+use crate::modbus::Operation;
+use crate::modbus_device_cache::{ModbusDeviceValues, RegisterBitmask};
+use crate::output;
+use crate::registers::RegisterIndex;
+use std::time::Duration;
+
+/// Show or change the Modbus communication settings of the device.
+#[derive(clap::Parser)]
+pub struct Args {
+    /// The new Modbus address (device ID) of the device.
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=247))]
+    new_address: Option<u8>,
+    /// The new baudrate of the device.
+    #[arg(long, value_parser = parse_baudrate)]
+    new_baudrate: Option<u32>,
+    /// The new parity of the device.
+    #[arg(long, value_enum)]
+    new_parity: Option<Parity>,
+    /// How long to keep trying to reach the device after changing the settings.
+    #[arg(long, default_value = "30s")]
+    confirm_timeout: humantime::Duration,
+    /// Apply the new settings without asking for a confirmation.
+    #[arg(long)]
+    force: bool,
+    #[clap(flatten)]
+    connection: connection::Args,
+    #[clap(flatten)]
+    output: output::Args,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("could establish client connection with the device")]
+    EstablishClient(#[source] crate::connection::Error),
+    #[error("communication with the device failed")]
+    Communicate(#[source] crate::connection::Error),
+    #[error("could not read the communication settings from the device")]
+    Incomplete,
+    #[error("could not read the confirmation")]
+    ReadConfirmation(#[source] std::io::Error),
+    #[error("the new settings were not confirmed, nothing was changed")]
+    NotConfirmed,
+    #[error("device rejected the new settings with exception {0}")]
+    Rejected(u8),
+    #[error("device could not be reached with the new settings")]
+    Unreachable,
+    #[error("could not record the changes in the journal")]
+    Journal(#[source] crate::journal::Error),
+    #[error(transparent)]
+    CreateOutput(crate::output::Error),
+    #[error(transparent)]
+    WriteOutput(crate::output::Error),
+    #[error(transparent)]
+    CommitOutput(crate::output::Error),
+}
+
+/// The baudrates selected by the values of `COMM_MODBUS_BAUD_RATE`.
+pub(crate) const BAUD_RATES: [u32; 11] =
+    [1200, 2400, 4800, 9600, 14400, 19200, 28800, 38400, 57600, 76800, 115200];
+
+/// The parities selected by the values of `COMM_MODBUS_PARITY`.
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, strum::FromRepr, strum::IntoStaticStr)]
+#[strum(serialize_all = "lowercase")]
+#[repr(u16)]
+pub(crate) enum Parity {
+    None = 0,
+    Even = 1,
+    Odd = 2,
+}
+
+fn parse_baudrate(s: &str) -> Result<u32, String> {
+    let baudrate = s.parse::<u32>().map_err(|e| e.to_string())?;
+    if !BAUD_RATES.contains(&baudrate) {
+        let supported = BAUD_RATES.map(|b| b.to_string()).join(", ");
+        return Err(format!("the device supports baudrates {supported}"));
+    }
+    Ok(baudrate)
+}
+
+/// The value of `COMM_MODBUS_BAUD_RATE` for a baudrate.
+fn baudrate_index(baudrate: u32) -> u16 {
+    BAUD_RATES.iter().position(|&b| b == baudrate).expect("baudrates are validated") as u16
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Settings {
+    address: u16,
+    baudrate: u16,
+    parity: u16,
+}
+
+impl Settings {
+    fn baudrate(&self) -> Option<u32> {
+        BAUD_RATES.get(usize::from(self.baudrate)).copied()
+    }
+
+    fn parity(&self) -> Option<Parity> {
+        Parity::from_repr(self.parity)
+    }
+}
+
+fn first_register() -> RegisterIndex {
+    register("COMM_MODBUS_ADDRESS")
+}
+
+async fn read_settings(connection: &Connection) -> Result<Settings, Error> {
+    let first = first_register().address();
+    let mut mask = RegisterBitmask::new();
+    for address in first..first + 3 {
+        mask.set(address);
+    }
+    let mut values = ModbusDeviceValues::new();
+    values.read_registers(connection, &mask).await.map_err(Error::Communicate)?;
+    let value = |offset| values.value_of_address(first + offset).ok_or(Error::Incomplete);
+    Ok(Settings { address: value(0)?, baudrate: value(1)?, parity: value(2)? })
+}
+
+/// Keep trying to read the settings over new connections until the deadline.
+async fn reconnect(args: &connection::Args, timeout: Duration) -> Option<Settings> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    // Give the device a moment to apply the settings before trying to reach it.
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    loop {
+        let attempt = async {
+            let connection = Connection::new(args.clone()).await.map_err(Error::EstablishClient)?;
+            read_settings(&connection).await
+        };
+        match tokio::time::timeout(Duration::from_secs(5), attempt).await {
+            Ok(Ok(settings)) => return Some(settings),
+            Ok(Err(e)) => tracing::debug!(
+                message = "device not reachable yet",
+                error = (&e as &dyn std::error::Error)
+            ),
+            Err(_) => tracing::debug!("device not reachable yet"),
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return None;
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+/// Describe the effects of the change and ask the user to confirm it, unless `force` is set.
+fn confirm(old: Settings, new: Settings, force: bool) -> Result<(), Error> {
+    let first = first_register().address();
+    let changed =
+        [(old.address, new.address), (old.baudrate, new.baudrate), (old.parity, new.parity)];
+    for (offset, (old, new)) in (0..).zip(changed) {
+        let Some(register) = RegisterIndex::from_address(first + offset) else { continue };
+        if old == new {
+            continue;
+        }
+        // Refusing the critical writes is up to the user here.
+        if let Ok(Some(effect)) | Err(effect) = check_critical(register, force) {
+            tracing::warn!(register = register.name(), value = new, effect, "safety-critical");
+        }
+    }
+    if force {
+        return Ok(());
+    }
+    let question = "Apply the new communication settings? [y/N]";
+    if !super::common::confirm(question, YES).map_err(Error::ReadConfirmation)? {
+        return Err(Error::NotConfirmed);
+    }
+    Ok(())
+}
+
+fn print_recovery(old: Settings, new: Settings) {
+    let describe = |s: Settings| {
+        let baudrate =
+            s.baudrate().map_or_else(|| format!("index {}", s.baudrate), |b| b.to_string());
+        let parity = s.parity().map_or_else(|| s.parity.to_string(), |p| <&str>::from(p).into());
+        (baudrate, parity)
+    };
+    let (new_baudrate, new_parity) = describe(new);
+    let (old_baudrate, old_parity) = describe(old);
+    eprintln!(
+        "The device did not respond after the change. To recover:\n\
+         \n\
+         - If the Modbus gateway has its own serial line settings, change them to {new_baudrate} \
+         baud with {new_parity} parity, then connect with `--device-id {} --baudrate \
+         {new_baudrate}`;\n\
+         - Otherwise restore the previous settings (address {}, {old_baudrate} baud, \
+         {old_parity} parity) using the Modbus settings menu of the control panel.",
+        new.address, old.address,
+    );
+}
+
+#[derive(serde::Serialize)]
+struct OutputSchema {
+    setting: &'static str,
+    register: &'static str,
+    raw: u16,
+    value: String,
+}
+
+fn print_settings(settings: Settings, output: output::Args) -> Result<(), Error> {
+    let mut output = output.to_output().map_err(Error::CreateOutput)?;
+    let heads = vec!["Setting", "Register", "Raw", "Value"];
+    output.table_headers(heads).map_err(Error::WriteOutput)?;
+    let rows = [
+        ("address", "COMM_MODBUS_ADDRESS", settings.address, settings.address.to_string()),
+        (
+            "baudrate",
+            "COMM_MODBUS_BAUD_RATE",
+            settings.baudrate,
+            settings.baudrate().map(|b| b.to_string()).unwrap_or_default(),
+        ),
+        (
+            "parity",
+            "COMM_MODBUS_PARITY",
+            settings.parity,
+            settings.parity().map(|p| <&str>::from(p).to_string()).unwrap_or_default(),
+        ),
+    ];
+    for (setting, register, raw, value) in rows {
+        output
+            .result(
+                || vec![setting.to_string(), register.to_string(), raw.to_string(), value.clone()],
+                || OutputSchema { setting, register, raw, value: value.clone() },
+            )
+            .map_err(Error::WriteOutput)?;
+    }
+    output.commit().map_err(Error::CommitOutput)
+}
+
+#[tokio::main(flavor = "current_thread")]
+pub async fn run(args: Args) -> Result<(), Error> {
+    let connection =
+        Connection::new(args.connection.clone()).await.map_err(Error::EstablishClient)?;
+    let old = read_settings(&connection).await?;
+    let new = Settings {
+        address: args.new_address.map_or(old.address, u16::from),
+        baudrate: args.new_baudrate.map_or(old.baudrate, baudrate_index),
+        parity: args.new_parity.map_or(old.parity, |p| p as u16),
+    };
+    if new == old {
+        return print_settings(old, args.output);
+    }
+    confirm(old, new, args.force)?;
+
+    tracing::info!("applying the new communication settings");
+    let operation = Operation::SetHoldings {
+        address: first_register().address(),
+        values: vec![new.address, new.baudrate, new.parity],
+    };
+    // The device may switch over before responding, so a missing response is expected.
+    match tokio::time::timeout(Duration::from_secs(5), connection.send(operation)).await {
+        Ok(Ok(Some(response))) => {
+            if let Some(code) = response.exception_code() {
+                return Err(Error::Rejected(code));
+            }
+        }
+        Ok(Ok(None)) | Err(_) => tracing::debug!("no response to the settings change"),
+        Ok(Err(e)) => return Err(Error::Communicate(e)),
+    }
+    // The old session is of no use anymore, stop it from retrying on its own.
+    connection.worker.abort();
+    drop(connection);
+    // Recorded before reconnecting, as the device has most likely switched over either way.
+    let first = first_register().address();
+    let changes =
+        [(old.address, new.address), (old.baudrate, new.baudrate), (old.parity, new.parity)];
+    let changes = (0..).zip(changes).filter_map(|(offset, (old, new))| {
+        let register = RegisterIndex::from_address(first + offset)?;
+        let data_type = register.data_type();
+        Some((register, data_type.from_word(old), data_type.from_word(new)))
+    });
+    crate::journal::record("comm-settings", changes).map_err(Error::Journal)?;
+
+    let mut reconnect_args = args.connection;
+    if new.address != old.address {
+        reconnect_args.set_device_id(new.address as u8);
+    }
+    if let Some(baudrate) = new.baudrate().filter(|_| new.baudrate != old.baudrate) {
+        reconnect_args.set_baudrate(baudrate);
+    }
+    match reconnect(&reconnect_args, *args.confirm_timeout).await {
+        Some(settings) if settings == new => print_settings(settings, args.output),
+        _ => {
+            print_recovery(old, new);
+            Err(Error::Unreachable)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn baudrates() {
+        assert_eq!(baudrate_index(1200), 0);
+        assert_eq!(baudrate_index(9600), 3);
+        assert_eq!(baudrate_index(115200), 10);
+        assert_eq!(parse_baudrate("19200"), Ok(19200));
+        assert!(parse_baudrate("19201").is_err());
+        assert!(parse_baudrate("fast").is_err());
+    }
+
+    #[test]
+    fn settings() {
+        let settings = Settings { address: 1, baudrate: baudrate_index(38400), parity: 2 };
+        assert_eq!(settings.baudrate(), Some(38400));
+        assert!(settings.parity() == Some(Parity::Odd));
+        assert_eq!(<&str>::from(Parity::Even), "even");
+        let unknown = Settings { address: 1, baudrate: 11, parity: 3 };
+        assert_eq!(unknown.baudrate(), None);
+        assert!(unknown.parity().is_none());
+    }
+
+    #[test]
+    fn registers_are_consecutive() {
+        let first = first_register().address();
+        assert_eq!(register("COMM_MODBUS_BAUD_RATE").address(), first + 1);
+        assert_eq!(register("COMM_MODBUS_PARITY").address(), first + 2);
+    }
+}
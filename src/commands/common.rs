@@ -0,0 +1,103 @@
+//! Helpers shared between the commands that change settings on the device.
+
+use crate::connection::Connection;
+use crate::modbus::{Operation, ResponseKind};
+use crate::registers::{RegisterIndex, Value};
+use std::io::BufRead as _;
+
+#[derive(thiserror::Error, Debug)]
+pub enum WriteError {
+    #[error("communication with the device failed")]
+    Communicate(#[source] crate::connection::Error),
+    #[error("device rejected the write to {0} with exception {1}")]
+    Rejected(&'static str, u8),
+}
+
+/// Look up a register the command relies on by its name.
+///
+/// Only meant for the names spelled out in the code, so a missing register is a bug.
+pub(crate) fn register(name: &str) -> RegisterIndex {
+    RegisterIndex::from_name(name).unwrap_or_else(|| panic!("register {name} is not known"))
+}
+
+/// The answers accepted for a `[y/N]` question.
+pub(crate) const YES: &[&str] = &["y", "yes"];
+
+/// Ask the user a question on the terminal and check whether the answer is one of `accepted`.
+///
+/// Letter case and the whitespace around the answer do not matter.
+pub(crate) fn confirm(question: &str, accepted: &[&str]) -> Result<bool, std::io::Error> {
+    eprint!("{question} ");
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    let answer = answer.trim().to_lowercase();
+    Ok(accepted.iter().any(|accepted| answer == accepted.to_lowercase()))
+}
+
+/// Refuse writing into a safety-critical register unless `force` is set.
+///
+/// Returns the effect of the write, as the error if it is refused, and otherwise for the caller to
+/// warn about.
+pub(crate) fn check_critical(
+    register: RegisterIndex,
+    force: bool,
+) -> Result<Option<&'static str>, &'static str> {
+    match register.critical_effect() {
+        Some(effect) if !force => Err(effect),
+        effect => Ok(effect),
+    }
+}
+
+/// Check the value against the range documented for the register.
+///
+/// On failure the allowed range is returned in the `min..=max` notation, with the missing bound
+/// left out for registers that only document one of them.
+pub(crate) fn check_range(register: RegisterIndex, value: Value) -> Result<(), String> {
+    let (min, max) = (register.minimum_value(), register.maximum_value());
+    if min.is_none_or(|min| value >= min) && max.is_none_or(|max| value <= max) {
+        return Ok(());
+    }
+    Err(match (min, max) {
+        (min, Some(max)) => format!("{}..={max}", min.map(|v| v.to_string()).unwrap_or_default()),
+        (min, None) => format!("{}..", min.map(|v| v.to_string()).unwrap_or_default()),
+    })
+}
+
+/// Write a single register, treating an exception response as a failure.
+pub(crate) async fn write(
+    connection: &Connection,
+    register: RegisterIndex,
+    value: u16,
+) -> Result<(), WriteError> {
+    let operation = Operation::SetHoldings { address: register.address(), values: vec![value] };
+    let response = connection.send_retrying(operation).await.map_err(WriteError::Communicate)?;
+    if let ResponseKind::ErrorCode(code) = response.kind {
+        return Err(WriteError::Rejected(register.name(), code));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges() {
+        let pband = register("DEMC_RH_SETTINGS_PBAND");
+        assert_eq!(check_range(pband, Value::U16(1)), Ok(()));
+        assert_eq!(check_range(pband, Value::U16(100)), Ok(()));
+        assert_eq!(check_range(pband, Value::U16(0)), Err("1..=100".into()));
+        assert_eq!(check_range(pband, Value::U16(101)), Err("1..=100".into()));
+        let unbounded = register("FAN_LEVEL_SAF_MIN_PRESSURE");
+        assert_eq!(check_range(unbounded, Value::U16(u16::MAX)), Ok(()));
+    }
+    #[test]
+    fn critical_registers() {
+        let clear = register("ALARM_FIRE_ALARM_CLEAR_ALARM");
+        let effect = clear.critical_effect().expect("clearing the fire alarm is critical");
+        assert_eq!(check_critical(clear, false), Err(effect));
+        assert_eq!(check_critical(clear, true), Ok(Some(effect)));
+        let pband = register("DEMC_RH_SETTINGS_PBAND");
+        assert_eq!(check_critical(pband, false), Ok(None));
+    }
+}
@@ -0,0 +1,333 @@
+//! Storing, restoring and resetting the whole device configuration.
+//!
+//! The device can keep a copy of its settings, called the user safe config, which it can later
+//! revert to. It can also be reset to the factory defaults. Each of these operations affects many
+//! settings at once, so the settings are always backed up into a snapshot first (which `restore`
+//! can write back), and the operation only proceeds after it is confirmed by typing its name.
+
+use super::common::{register, write};
+use crate::connection::{self, Connection};
+use crate::modbus_device_cache::{ModbusDeviceValues, RegisterBitmask};
+use crate::output;
+use crate::snapshot::{self, Number, Snapshot};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Store, restore or reset the whole device configuration.
+#[derive(clap::Parser)]
+pub struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(clap::Subcommand, strum::IntoStaticStr)]
+#[strum(serialize_all = "kebab-case")]
+enum Command {
+    /// Store the current settings as the user safe config.
+    SaveUserSafe {
+        #[clap(flatten)]
+        safeguards: Safeguards,
+        #[clap(flatten)]
+        connection: connection::Args,
+    },
+    /// Revert the settings to the previously stored user safe config.
+    RestoreUserSafe {
+        #[clap(flatten)]
+        safeguards: Safeguards,
+        #[clap(flatten)]
+        connection: connection::Args,
+        #[clap(flatten)]
+        output: output::Args,
+    },
+    /// Reset the settings to the factory defaults.
+    FactoryReset {
+        #[clap(flatten)]
+        safeguards: Safeguards,
+        #[clap(flatten)]
+        connection: connection::Args,
+        #[clap(flatten)]
+        output: output::Args,
+    },
+}
+
+#[derive(clap::Parser)]
+struct Safeguards {
+    /// Where to back up the settings before making any changes.
+    ///
+    /// Defaults to a timestamped file in the current directory.
+    #[arg(long)]
+    backup: Option<PathBuf>,
+    /// The backup format. Inferred from the backup file extension by default.
+    #[arg(long, value_enum)]
+    snapshot_format: Option<snapshot::Format>,
+    /// How long to wait for the device to complete the operation.
+    #[arg(long, default_value = "1m")]
+    verify_timeout: humantime::Duration,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("could establish client connection with the device")]
+    EstablishClient(#[source] crate::connection::Error),
+    #[error("communication with the device failed")]
+    Communicate(#[source] crate::connection::Error),
+    #[error("could not back up the settings")]
+    Backup(#[source] crate::snapshot::Error),
+    #[error("could not read the confirmation")]
+    ReadConfirmation(#[source] std::io::Error),
+    #[error("operation was not confirmed, nothing was changed")]
+    NotConfirmed,
+    #[error("device does not have a user safe config stored")]
+    NoUserSafeConfig,
+    #[error(transparent)]
+    Write(super::common::WriteError),
+    #[error("device did not confirm storing the user safe config in time")]
+    NotStored,
+    #[error("device did not respond in time after the operation")]
+    NoResponse,
+    #[error("could not record the changes in the journal")]
+    Journal(#[source] crate::journal::Error),
+    #[error(transparent)]
+    CreateOutput(crate::output::Error),
+    #[error(transparent)]
+    WriteOutput(crate::output::Error),
+    #[error(transparent)]
+    CommitOutput(crate::output::Error),
+}
+
+/// The value `FACTORY_RESET` must be set to for the reset to happen.
+const FACTORY_RESET_MAGIC: u16 = 3228;
+
+/// How long to give the device to respond to a single read while waiting for it to come back.
+const POLL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The state of the user safe config as reported by the device.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct UserSafeState {
+    /// Whether a user safe config has been stored.
+    valid: bool,
+    /// Whether a request to store the user safe config is still pending.
+    store_pending: bool,
+}
+
+async fn user_safe_state(connection: &Connection) -> Result<UserSafeState, Error> {
+    let (valid, pending) = (register("USER_SAFE_CONFIG_VALID"), register("SET_USER_SAFE_CONFIG"));
+    let mut mask = RegisterBitmask::new();
+    mask.set(valid.address());
+    mask.set(pending.address());
+    let mut values = ModbusDeviceValues::new();
+    values.read_registers(connection, &mask).await.map_err(Error::Communicate)?;
+    let is_set = |r| values.value_of(r).is_some_and(|v| v.into_inner() != 0);
+    Ok(UserSafeState { valid: is_set(valid), store_pending: is_set(pending) })
+}
+
+/// Whether the device has stored the user safe config requested while it was in `before` state.
+///
+/// `USER_SAFE_CONFIG_VALID` stays set if a config had been stored earlier, so it alone cannot
+/// confirm the new one was stored. The device clears `SET_USER_SAFE_CONFIG` once it is done.
+fn user_safe_stored(before: UserSafeState, now: UserSafeState) -> bool {
+    now.valid && (!before.valid || !now.store_pending)
+}
+
+/// Save all the settings into the backup file and return them.
+async fn backup(
+    connection: &Connection,
+    safeguards: &Safeguards,
+    command: &str,
+) -> Result<Snapshot, Error> {
+    let path = safeguards.backup.clone().unwrap_or_else(|| {
+        let now = jiff::Zoned::now().strftime("%Y%m%dT%H%M%S");
+        PathBuf::from(format!("systemair-{command}-{now}.json"))
+    });
+    let format =
+        safeguards.snapshot_format.unwrap_or_else(|| snapshot::Format::from_path(Some(&path)));
+    let snapshot = super::dump::take(connection).await.map_err(Error::Communicate)?;
+    snapshot.save(Some(&path), format).map_err(Error::Backup)?;
+    eprintln!("Backed up the current settings to {}", path.display());
+    Ok(snapshot)
+}
+
+/// Ask the user to type the command name to proceed.
+fn confirm(command: &str, effect: &str) -> Result<(), Error> {
+    eprintln!("{effect}");
+    let question = format!("Type `{command}` to continue:");
+    if !super::common::confirm(&question, &[command]).map_err(Error::ReadConfirmation)? {
+        return Err(Error::NotConfirmed);
+    }
+    Ok(())
+}
+
+/// Wait until the device responds again after an operation that may restart it, and read back
+/// its settings.
+async fn wait_for_settings(
+    connection: &mut Connection,
+    connection_args: &connection::Args,
+    timeout: Duration,
+) -> Result<Snapshot, Error> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    // Give the device a moment to act on the request before checking on it.
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    loop {
+        // Only check that the device responds under the timeout. Reading out all of the settings
+        // can take much longer than that on slow links.
+        match tokio::time::timeout(POLL_TIMEOUT, user_safe_state(connection)).await {
+            Ok(Ok(_)) => return super::dump::take(connection).await.map_err(Error::Communicate),
+            Ok(Err(e)) => {
+                tracing::debug!(
+                    message = "device not responding yet, will reconnect",
+                    error = (&e as &dyn std::error::Error)
+                );
+                *connection = Connection::new(connection_args.clone())
+                    .await
+                    .map_err(Error::EstablishClient)?;
+            }
+            Err(_) => tracing::debug!("device not responding yet"),
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Err(Error::NoResponse);
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+#[derive(serde::Serialize)]
+struct OutputSchema<'a> {
+    address: u16,
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    before: Option<Number>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    after: Option<Number>,
+}
+
+/// Record the settings that differ between the two snapshots in the journal.
+fn record_changes(command: &str, before: &Snapshot, after: &Snapshot) -> Result<(), Error> {
+    let before = before.registers.iter().map(|e| (e.address, e)).collect::<BTreeMap<_, _>>();
+    let changes = after.registers.iter().filter_map(|after| {
+        let register = after.register().ok()?;
+        let before = before.get(&after.address)?.parse_value(register).ok()?;
+        Some((register, before, after.parse_value(register).ok()?))
+    });
+    crate::journal::record(command, changes).map_err(Error::Journal)
+}
+
+/// Output the settings that differ between the two snapshots.
+fn print_changes(before: &Snapshot, after: &Snapshot, output: output::Args) -> Result<(), Error> {
+    let mut changes = BTreeMap::<_, (&str, Option<Number>, Option<Number>)>::new();
+    for entry in &before.registers {
+        changes.entry(entry.address).or_insert((&entry.name, None, None)).1 = Some(entry.value);
+    }
+    for entry in &after.registers {
+        changes.entry(entry.address).or_insert((&entry.name, None, None)).2 = Some(entry.value);
+    }
+    let mut output = output.to_output().map_err(Error::CreateOutput)?;
+    let heads = vec!["Address", "Name", "Before", "After"];
+    output.table_headers(heads).map_err(Error::WriteOutput)?;
+    let mut changed = 0;
+    for (address, (name, before, after)) in changes {
+        if before == after {
+            continue;
+        }
+        changed += 1;
+        output
+            .result(
+                || {
+                    vec![
+                        address.to_string(),
+                        name.to_string(),
+                        before.map(|v| v.to_string()).unwrap_or_default(),
+                        after.map(|v| v.to_string()).unwrap_or_default(),
+                    ]
+                },
+                || OutputSchema { address, name, before, after },
+            )
+            .map_err(Error::WriteOutput)?;
+    }
+    output.commit().map_err(Error::CommitOutput)?;
+    eprintln!("{changed} setting(s) changed");
+    Ok(())
+}
+
+#[tokio::main(flavor = "current_thread")]
+pub async fn run(args: Args) -> Result<(), Error> {
+    let name = <&'static str>::from(&args.command);
+    match args.command {
+        Command::SaveUserSafe { safeguards, connection } => {
+            let connection = Connection::new(connection).await.map_err(Error::EstablishClient)?;
+            backup(&connection, &safeguards, name).await?;
+            confirm(name, "The current settings will replace the stored user safe config.")?;
+            let before = user_safe_state(&connection).await?;
+            write(&connection, register("SET_USER_SAFE_CONFIG"), 1).await.map_err(Error::Write)?;
+            let deadline = tokio::time::Instant::now() + *safeguards.verify_timeout;
+            while !user_safe_stored(before, user_safe_state(&connection).await?) {
+                if tokio::time::Instant::now() >= deadline {
+                    return Err(Error::NotStored);
+                }
+                tokio::time::sleep(Duration::from_millis(500)).await;
+            }
+            eprintln!("Stored the current settings as the user safe config");
+            Ok(())
+        }
+        Command::RestoreUserSafe { safeguards, connection: connection_args, output } => {
+            let mut connection =
+                Connection::new(connection_args.clone()).await.map_err(Error::EstablishClient)?;
+            if !user_safe_state(&connection).await?.valid {
+                return Err(Error::NoUserSafeConfig);
+            }
+            let before = backup(&connection, &safeguards, name).await?;
+            confirm(name, "All settings will be reverted to the stored user safe config.")?;
+            write(&connection, register("ACTIVATE_USER_SAFE_CONFIG"), 1)
+                .await
+                .map_err(Error::Write)?;
+            let after =
+                wait_for_settings(&mut connection, &connection_args, *safeguards.verify_timeout)
+                    .await?;
+            record_changes(name, &before, &after)?;
+            print_changes(&before, &after, output)
+        }
+        Command::FactoryReset { safeguards, connection: connection_args, output } => {
+            let mut connection =
+                Connection::new(connection_args.clone()).await.map_err(Error::EstablishClient)?;
+            let before = backup(&connection, &safeguards, name).await?;
+            confirm(
+                name,
+                "All settings will be reset to the factory defaults. The device may need to be \
+                 set up again, including its Modbus communication settings.",
+            )?;
+            write(&connection, register("FACTORY_RESET"), FACTORY_RESET_MAGIC)
+                .await
+                .map_err(Error::Write)?;
+            let after =
+                wait_for_settings(&mut connection, &connection_args, *safeguards.verify_timeout)
+                    .await?;
+            record_changes(name, &before, &after)?;
+            print_changes(&before, &after, output)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const fn state(valid: bool, store_pending: bool) -> UserSafeState {
+        UserSafeState { valid, store_pending }
+    }
+
+    #[test]
+    fn first_user_safe_config() {
+        let before = state(false, false);
+        assert!(!user_safe_stored(before, state(false, true)));
+        assert!(user_safe_stored(before, state(true, true)));
+        assert!(user_safe_stored(before, state(true, false)));
+    }
+
+    #[test]
+    fn replaced_user_safe_config() {
+        let before = state(true, false);
+        // The config stored earlier is still valid while the request is pending.
+        assert!(!user_safe_stored(before, state(true, true)));
+        assert!(user_safe_stored(before, state(true, false)));
+    }
+}
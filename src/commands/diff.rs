@@ -0,0 +1,253 @@
+//! Compare register values between two snapshots, or between a snapshot and the device.
+//!
+//! Values are compared after normalizing them through the register's data type, so that the same
+//! setting written down differently (`21` against `21.0`) is not reported as a change. Entries
+//! naming registers this tool does not know about are reported as such rather than rejected, which
+//! keeps snapshots from other firmware versions comparable.
+
+use crate::connection::{self, Connection};
+use crate::modbus_device_cache::{ModbusDeviceValues, RegisterBitmask};
+use crate::output;
+use crate::registers::RegisterIndex;
+use crate::snapshot::{self, Number, Snapshot};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Compare register values between two snapshots, or a snapshot and the device.
+#[derive(clap::Parser)]
+pub struct Args {
+    /// The snapshot to compare against.
+    old: PathBuf,
+    /// The snapshot with the new values. Values are read from the device when not specified.
+    new: Option<PathBuf>,
+    /// The snapshot format. Inferred from the file extension by default.
+    #[arg(long, value_enum)]
+    snapshot_format: Option<snapshot::Format>,
+    /// Only compare the specified registers.
+    ///
+    /// By default all registers present in either snapshot are compared. When reading from the
+    /// device, all writable settings are read.
+    #[arg(long, short = 'r', value_delimiter = ',')]
+    registers: Vec<String>,
+    /// Also output the registers that did not change.
+    #[arg(long)]
+    all: bool,
+    #[clap(flatten)]
+    connection: connection::OptionalArgs,
+    #[clap(flatten)]
+    output: output::Args,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("could not load the snapshot")]
+    Load(#[source] crate::snapshot::Error),
+    #[error("register `{0}` does not match any known register")]
+    RegisterNotFound(String),
+    #[error("a connection to the device is required when only one snapshot is given")]
+    ConnectionRequired,
+    #[error("could establish client connection with the device")]
+    EstablishClient(#[source] crate::connection::Error),
+    #[error("communication with the device failed")]
+    Communicate(#[source] crate::connection::Error),
+    #[error(transparent)]
+    CreateOutput(crate::output::Error),
+    #[error(transparent)]
+    WriteOutput(crate::output::Error),
+    #[error(transparent)]
+    CommitOutput(crate::output::Error),
+}
+
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq, strum::Display)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+enum Status {
+    Same,
+    Changed,
+    /// The register only has a value in the new listing.
+    MissingInOld,
+    /// The register only has a value in the old listing.
+    MissingInNew,
+    /// The register is not known to this tool.
+    Unknown,
+}
+
+#[derive(serde::Serialize)]
+struct OutputSchema<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    address: Option<u16>,
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old: Option<Number>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new: Option<Number>,
+    status: Status,
+}
+
+/// Register values keyed by their name.
+///
+/// Values of the known registers are normalized through their data type, so that e.g. `21` and
+/// `21.0` compare equal for a temperature register.
+type Listing = BTreeMap<String, Number>;
+
+fn load(path: &Path, format: Option<snapshot::Format>) -> Result<Listing, Error> {
+    let format = format.unwrap_or_else(|| snapshot::Format::from_path(Some(path)));
+    let snapshot = Snapshot::load(path, format).map_err(Error::Load)?;
+    Ok(listing(snapshot))
+}
+
+fn listing(snapshot: Snapshot) -> Listing {
+    snapshot
+        .registers
+        .into_iter()
+        .map(|entry| {
+            let value = entry
+                .register()
+                .and_then(|register| entry.parse_value(register))
+                .map(Number::from)
+                .unwrap_or(entry.value);
+            (entry.name, value)
+        })
+        .collect()
+}
+
+/// Pair up the registers of both listings, ordered by their address.
+///
+/// Registers not known to this tool sort last. When `selected` is not empty, only those registers
+/// are compared.
+fn compare<'a>(
+    old: &'a Listing,
+    new: &'a Listing,
+    selected: &[RegisterIndex],
+) -> Vec<(&'a str, Status)> {
+    let mut names = old.keys().chain(new.keys()).map(String::as_str).collect::<Vec<_>>();
+    if !selected.is_empty() {
+        names.retain(|name| selected.iter().any(|r| r.name() == *name));
+    }
+    names.sort_by_cached_key(|name| {
+        (RegisterIndex::from_name(name).map(|r| r.address()).unwrap_or(u16::MAX), *name)
+    });
+    names.dedup();
+    names
+        .into_iter()
+        .map(|name| {
+            let status = match (old.get(name), new.get(name)) {
+                _ if RegisterIndex::from_name(name).is_none() => Status::Unknown,
+                (None, _) => Status::MissingInOld,
+                (_, None) => Status::MissingInNew,
+                (Some(old), Some(new)) if old == new => Status::Same,
+                (Some(_), Some(_)) => Status::Changed,
+            };
+            (name, status)
+        })
+        .collect()
+}
+
+#[tokio::main(flavor = "current_thread")]
+pub async fn run(args: Args) -> Result<(), Error> {
+    let mut selected = vec![];
+    for name in &args.registers {
+        let register =
+            RegisterIndex::from_name(name).ok_or_else(|| Error::RegisterNotFound(name.clone()))?;
+        selected.push(register);
+    }
+    let old = load(&args.old, args.snapshot_format)?;
+    let new = match &args.new {
+        Some(path) => load(path, args.snapshot_format)?,
+        None => {
+            let connection_args = args.connection.0.ok_or(Error::ConnectionRequired)?;
+            let registers = if selected.is_empty() {
+                RegisterIndex::all().filter(|r| super::dump::is_setting(*r)).collect()
+            } else {
+                selected.clone()
+            };
+            let mut mask = RegisterBitmask::new();
+            for register in &registers {
+                mask.set(register.address());
+            }
+            let connection =
+                Connection::new(connection_args).await.map_err(Error::EstablishClient)?;
+            let mut values = ModbusDeviceValues::new();
+            values.read_registers(&connection, &mask).await.map_err(Error::Communicate)?;
+            registers
+                .into_iter()
+                .filter_map(|r| Some((r.name().to_string(), values.value_of(r)?.into())))
+                .collect()
+        }
+    };
+
+    let mut output = args.output.to_output().map_err(Error::CreateOutput)?;
+    let heads = vec!["Address", "Name", "Old", "New", "Status"];
+    output.table_headers(heads).map_err(Error::WriteOutput)?;
+    for (name, status) in compare(&old, &new, &selected) {
+        let address = RegisterIndex::from_name(name).map(|r| r.address());
+        let (old, new) = (old.get(name).copied(), new.get(name).copied());
+        if status == Status::Same && !args.all {
+            continue;
+        }
+        output
+            .result(
+                || {
+                    vec![
+                        address.map(|a| a.to_string()).unwrap_or_default(),
+                        name.to_string(),
+                        old.map(|v| v.to_string()).unwrap_or_default(),
+                        new.map(|v| v.to_string()).unwrap_or_default(),
+                        status.to_string(),
+                    ]
+                },
+                || OutputSchema { address, name, old, new, status },
+            )
+            .map_err(Error::WriteOutput)?;
+    }
+    output.commit().map_err(Error::CommitOutput)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::Entry;
+
+    fn entry(name: &str, value: Number) -> Entry {
+        let address = RegisterIndex::from_name(name).map(|r| r.address()).unwrap_or(0);
+        Entry { name: name.into(), address, value }
+    }
+
+    fn snapshot(entries: Vec<Entry>) -> Listing {
+        listing(Snapshot::new(entries))
+    }
+
+    #[test]
+    fn normalizes_values() {
+        let old = snapshot(vec![entry("USERMODE_CROWDED_T_OFFSET", Number::Integer(-5))]);
+        let new = snapshot(vec![entry("USERMODE_CROWDED_T_OFFSET", Number::Float(-5.0))]);
+        assert_eq!(compare(&old, &new, &[]), [("USERMODE_CROWDED_T_OFFSET", Status::Same)]);
+    }
+
+    #[test]
+    fn statuses() {
+        let old = snapshot(vec![
+            entry("DEMC_RH_SETTINGS_SP_WINTER", Number::Integer(40)),
+            entry("DEMC_RH_SETTINGS_PBAND", Number::Integer(5)),
+            entry("DEMC_RH_SETTINGS_SP_SUMMER", Number::Integer(60)),
+            entry("NOT_A_REGISTER", Number::Integer(1)),
+        ]);
+        let new = snapshot(vec![
+            entry("DEMC_RH_SETTINGS_PBAND", Number::Integer(10)),
+            entry("DEMC_RH_SETTINGS_SP_SUMMER", Number::Integer(60)),
+            entry("USERMODE_CROWDED_T_OFFSET", Number::Float(-2.5)),
+        ]);
+        assert_eq!(
+            compare(&old, &new, &[]),
+            [
+                ("DEMC_RH_SETTINGS_PBAND", Status::Changed),
+                ("DEMC_RH_SETTINGS_SP_SUMMER", Status::Same),
+                ("DEMC_RH_SETTINGS_SP_WINTER", Status::MissingInNew),
+                ("USERMODE_CROWDED_T_OFFSET", Status::MissingInOld),
+                ("NOT_A_REGISTER", Status::Unknown),
+            ]
+        );
+        let pband = RegisterIndex::from_name("DEMC_RH_SETTINGS_PBAND").unwrap();
+        assert_eq!(compare(&old, &new, &[pband]), [("DEMC_RH_SETTINGS_PBAND", Status::Changed)]);
+    }
+}
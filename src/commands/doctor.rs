@@ -0,0 +1,525 @@
+//! Checks for the device configuration this tool expects.
+//!
+//! Other commands assume that the device uses SI units, has no locks or passwords enabled, has a
+//! reasonably accurate clock and that the connection settings match the Modbus configuration of
+//! the device. Each check reports whether this holds and, if not, what to do about it.
+
+use super::comm_settings::{BAUD_RATES, Parity};
+use super::common::{check_critical, register};
+use crate::connection::{self, Connection};
+use crate::modbus::{Operation, ResponseKind};
+use crate::modbus_device_cache::{ModbusDeviceValues, RegisterBitmask};
+use crate::output;
+use crate::registers::RegisterIndex;
+
+/// Check that the device is configured the way this tool expects it to be.
+#[derive(clap::Parser)]
+pub struct Args {
+    /// Apply the fixes for the problems found.
+    ///
+    /// Problems without an automatic fix are only reported.
+    #[arg(long)]
+    fix: bool,
+    /// The largest acceptable difference between the device clock and the local time.
+    #[arg(long, default_value = "2m")]
+    max_clock_drift: humantime::Duration,
+    #[clap(flatten)]
+    connection: connection::Args,
+    #[clap(flatten)]
+    output: output::Args,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("could establish client connection with the device")]
+    EstablishClient(#[source] crate::connection::Error),
+    #[error("communication with the device failed")]
+    Communicate(#[source] crate::connection::Error),
+    #[error("device rejected the write to {0} with exception {1}")]
+    Rejected(&'static str, u8),
+    #[error("writing {0} {1}, use `write --force` if this is intended")]
+    Critical(&'static str, &'static str),
+    #[error("{0} problem(s) found")]
+    ProblemsFound(usize),
+    #[error("could not record the changes in the journal")]
+    Journal(#[source] crate::journal::Error),
+    #[error(transparent)]
+    CreateOutput(crate::output::Error),
+    #[error(transparent)]
+    WriteOutput(crate::output::Error),
+    #[error(transparent)]
+    CommitOutput(crate::output::Error),
+}
+
+const CLOCK_REGISTERS: [&str; 6] =
+    ["TIME_YEAR", "TIME_MONTH", "TIME_DAY", "TIME_HOUR", "TIME_MINUTE", "TIME_SECOND"];
+
+/// Registers that are `1` when a menu is unlocked.
+///
+/// Unlike the password flags, these read `0` when the menu is locked, as described in the
+/// Modbus variable list of the device (e.g. "0=menu locked, 1=menu not locked" for
+/// `LOCKED_FILTER`).
+const LOCKS: [(&str, &str); 3] = [
+    ("LOCKED_USER", "user menu lock"),
+    ("LOCKED_FILTER", "filter menu lock"),
+    ("LOCKED_WEEK_SCHEDULE", "week schedule menu lock"),
+];
+
+/// Registers that are `1` when a password is required.
+const PASSWORDS: [(&str, &str); 3] = [
+    ("PASSWD_USER_LEVEL_REQUIRED", "home screen password"),
+    ("PASSWD_FILTER_REQUIRED", "filter menu password"),
+    ("PASSWD_WEEK_SCHEDULE_REQUIRED", "week schedule menu password"),
+];
+
+const OTHER_REGISTERS: [&str; 8] = [
+    "SYSTEM_UNIT_FLOW",
+    "SYSTEM_UNIT_PRESSURE",
+    "SYSTEM_UNIT_TEMPERATURE",
+    "SUW_REQUIRED",
+    "USER_SAFE_CONFIG_VALID",
+    "COMM_MODBUS_ADDRESS",
+    "COMM_MODBUS_BAUD_RATE",
+    "COMM_MODBUS_PARITY",
+];
+
+enum Fix {
+    Set(&'static str, u16),
+    /// Set the device clock to the current local time.
+    SyncClock,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, strum::IntoStaticStr)]
+#[strum(serialize_all = "kebab-case")]
+enum Status {
+    Ok,
+    Problem,
+    Fixed,
+    Unknown,
+}
+
+struct Finding {
+    check: &'static str,
+    status: Status,
+    detail: String,
+    suggestion: Option<String>,
+    fix: Option<Fix>,
+}
+
+impl Finding {
+    fn ok(check: &'static str, detail: impl Into<String>) -> Self {
+        Self { check, status: Status::Ok, detail: detail.into(), suggestion: None, fix: None }
+    }
+
+    fn problem(check: &'static str, detail: impl Into<String>, suggestion: String) -> Self {
+        Self {
+            check,
+            status: Status::Problem,
+            detail: detail.into(),
+            suggestion: Some(suggestion),
+            fix: None,
+        }
+    }
+
+    fn with_fix(mut self, fix: Fix) -> Self {
+        self.fix = Some(fix);
+        self
+    }
+}
+
+#[derive(serde::Serialize)]
+struct OutputSchema<'a> {
+    check: &'static str,
+    status: &'static str,
+    detail: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suggestion: Option<&'a str>,
+}
+
+struct Context {
+    device_id: u8,
+    baudrate: u32,
+    max_clock_drift: jiff::SignedDuration,
+}
+
+fn check_unit(
+    values: &ModbusDeviceValues,
+    check: &'static str,
+    name: &'static str,
+    units: &[&str],
+    accepted: &[u16],
+) -> Finding {
+    let Some(value) = values.value_of(register(name)).map(|v| v.into_inner()) else {
+        return unreadable(check, name);
+    };
+    let unit = units.get(usize::from(value)).map_or_else(|| value.to_string(), |u| u.to_string());
+    if accepted.contains(&value) {
+        Finding::ok(check, unit)
+    } else {
+        Finding::problem(check, unit, format!("set {name} to {}", accepted[0]))
+            .with_fix(Fix::Set(name, accepted[0]))
+    }
+}
+
+fn unreadable(check: &'static str, name: &str) -> Finding {
+    Finding {
+        check,
+        status: Status::Unknown,
+        detail: format!("could not read {name}"),
+        suggestion: None,
+        fix: None,
+    }
+}
+
+fn check_clock(values: &ModbusDeviceValues, context: &Context) -> Finding {
+    let check = "clock";
+    let words = CLOCK_REGISTERS
+        .iter()
+        .map(|name| values.value_of(register(name)).map(|v| v.into_inner()))
+        .collect::<Option<Vec<_>>>();
+    let Some(&[year, month, day, hour, minute, second]) = words.as_deref() else {
+        return unreadable(check, "the device clock");
+    };
+    let suggestion = "set the device clock to the local time".to_string();
+    let device = (|| {
+        let date = jiff::civil::Date::new(
+            year.try_into().ok()?,
+            month.try_into().ok()?,
+            day.try_into().ok()?,
+        );
+        let time = jiff::civil::Time::new(
+            hour.try_into().ok()?,
+            minute.try_into().ok()?,
+            second.try_into().ok()?,
+            0,
+        );
+        Some(date.ok()?.to_datetime(time.ok()?))
+    })();
+    let Some(device) = device else {
+        return Finding::problem(check, "the device clock is not set to a valid time", suggestion)
+            .with_fix(Fix::SyncClock);
+    };
+    let local = jiff::Zoned::now().datetime();
+    let drift = device.duration_since(local);
+    let rounded = drift.round(jiff::Unit::Second).unwrap_or(drift);
+    let detail = match rounded.signum() {
+        0 => format!("{device}, in sync with the local time"),
+        1 => format!("{device}, {:#} ahead of the local time", rounded.abs()),
+        _ => format!("{device}, {:#} behind the local time", rounded.abs()),
+    };
+    if drift.abs() <= context.max_clock_drift {
+        Finding::ok(check, detail)
+    } else {
+        Finding::problem(check, detail, suggestion).with_fix(Fix::SyncClock)
+    }
+}
+
+fn check_comm(values: &ModbusDeviceValues, context: &Context) -> Vec<Finding> {
+    let mut findings = vec![];
+    let value = |name| values.value_of(register(name)).map(|v| v.into_inner());
+    findings.push(match value("COMM_MODBUS_ADDRESS") {
+        None => unreadable("modbus address", "COMM_MODBUS_ADDRESS"),
+        Some(address) if address == u16::from(context.device_id) => {
+            Finding::ok("modbus address", address.to_string())
+        }
+        Some(address) => Finding::problem(
+            "modbus address",
+            format!("{address}, but requests are sent to device ID {}", context.device_id),
+            format!("connect with `--device-id {address}`"),
+        ),
+    });
+    findings.push(match value("COMM_MODBUS_BAUD_RATE") {
+        None => unreadable("modbus baud rate", "COMM_MODBUS_BAUD_RATE"),
+        Some(index) => match BAUD_RATES.get(usize::from(index)) {
+            None => Finding::ok("modbus baud rate", format!("unknown setting {index}")),
+            Some(&baud) if baud == context.baudrate => {
+                Finding::ok("modbus baud rate", baud.to_string())
+            }
+            Some(&baud) => Finding::problem(
+                "modbus baud rate",
+                format!("{baud}, but requests are paced for {}", context.baudrate),
+                format!("connect with `--baudrate {baud}`"),
+            ),
+        },
+    });
+    findings.push(match value("COMM_MODBUS_PARITY") {
+        None => unreadable("modbus parity", "COMM_MODBUS_PARITY"),
+        Some(parity) => Finding::ok(
+            "modbus parity",
+            Parity::from_repr(parity)
+                .map_or_else(|| parity.to_string(), |p| <&str>::from(p).to_string()),
+        ),
+    });
+    findings
+}
+
+fn diagnose(values: &ModbusDeviceValues, context: &Context) -> Vec<Finding> {
+    let value = |name| values.value_of(register(name)).map(|v| v.into_inner());
+    let mut findings = vec![
+        check_unit(values, "flow unit", "SYSTEM_UNIT_FLOW", &["l/s", "m³/h", "cfm"], &[0, 1]),
+        check_unit(values, "pressure unit", "SYSTEM_UNIT_PRESSURE", &["Pa", "inH₂O"], &[0]),
+        check_unit(values, "temperature unit", "SYSTEM_UNIT_TEMPERATURE", &["°C", "°F"], &[0]),
+    ];
+    for (name, check) in LOCKS {
+        findings.push(match value(name) {
+            None => unreadable(check, name),
+            Some(1) => Finding::ok(check, "unlocked"),
+            Some(_) => Finding::problem(check, "locked", format!("set {name} to 1"))
+                .with_fix(Fix::Set(name, 1)),
+        });
+    }
+    for (name, check) in PASSWORDS {
+        findings.push(match value(name) {
+            None => unreadable(check, name),
+            Some(0) => Finding::ok(check, "not required"),
+            Some(_) => Finding::problem(check, "required", format!("set {name} to 0"))
+                .with_fix(Fix::Set(name, 0)),
+        });
+    }
+    findings.push(match value("SUW_REQUIRED") {
+        None => unreadable("start-up wizard", "SUW_REQUIRED"),
+        Some(0) => Finding::ok("start-up wizard", "completed"),
+        Some(_) => Finding::problem(
+            "start-up wizard",
+            "pending",
+            "complete the wizard on the control panel, or set SUW_REQUIRED to 0".into(),
+        )
+        .with_fix(Fix::Set("SUW_REQUIRED", 0)),
+    });
+    findings.push(match value("USER_SAFE_CONFIG_VALID") {
+        None => unreadable("user safe config", "USER_SAFE_CONFIG_VALID"),
+        Some(0) => Finding::problem(
+            "user safe config",
+            "not stored",
+            "once the device works as intended, store its settings by setting \
+             SET_USER_SAFE_CONFIG to 1"
+                .into(),
+        ),
+        Some(_) => Finding::ok("user safe config", "stored"),
+    });
+    findings.push(check_clock(values, context));
+    findings.extend(check_comm(values, context));
+    findings
+}
+
+async fn read_values(connection: &Connection) -> Result<ModbusDeviceValues, Error> {
+    let mut mask = RegisterBitmask::new();
+    let names = CLOCK_REGISTERS
+        .into_iter()
+        .chain(LOCKS.map(|(name, _)| name))
+        .chain(PASSWORDS.map(|(name, _)| name))
+        .chain(OTHER_REGISTERS);
+    for name in names {
+        mask.set(register(name).address());
+    }
+    let mut values = ModbusDeviceValues::new();
+    values.read_registers(connection, &mask).await.map_err(Error::Communicate)?;
+    Ok(values)
+}
+
+async fn apply(connection: &Connection, fix: &Fix) -> Result<(), Error> {
+    let (first, values) = match *fix {
+        Fix::Set(name, value) => (name, vec![value]),
+        Fix::SyncClock => {
+            let now = jiff::Zoned::now().datetime();
+            let values = [now.year(), now.month().into(), now.day().into()]
+                .into_iter()
+                .chain([now.hour(), now.minute(), now.second()].map(i16::from))
+                .map(|v| v as u16)
+                .collect();
+            (CLOCK_REGISTERS[0], values)
+        }
+    };
+    let first = register(first);
+    for address in (first.address()..).take(values.len()) {
+        let Some(register) = RegisterIndex::from_address(address) else { continue };
+        check_critical(register, false)
+            .map_err(|effect| Error::Critical(register.name(), effect))?;
+    }
+    tracing::info!(register = first.name(), ?values, "applying fix");
+    let operation = Operation::SetHoldings { address: first.address(), values };
+    let response = connection.send_retrying(operation).await.map_err(Error::Communicate)?;
+    if let ResponseKind::ErrorCode(code) = response.kind {
+        return Err(Error::Rejected(first.name(), code));
+    }
+    Ok(())
+}
+
+#[tokio::main(flavor = "current_thread")]
+pub async fn run(args: Args) -> Result<(), Error> {
+    let connection = Connection::new(args.connection).await.map_err(Error::EstablishClient)?;
+    let context = Context {
+        device_id: connection.device_id(),
+        baudrate: connection.baudrate(),
+        max_clock_drift: jiff::SignedDuration::try_from(*args.max_clock_drift)
+            .unwrap_or(jiff::SignedDuration::MAX),
+    };
+    let values = read_values(&connection).await?;
+    let mut findings = diagnose(&values, &context);
+    if args.fix {
+        let mut applied = false;
+        let mut fixed = vec![];
+        for finding in &findings {
+            if let (Status::Problem, Some(fix)) = (finding.status, &finding.fix) {
+                apply(&connection, fix).await?;
+                if let Fix::Set(name, _) = fix {
+                    fixed.push(register(name));
+                }
+                applied = true;
+            }
+        }
+        if applied {
+            let after = read_values(&connection).await?;
+            let changes = fixed
+                .into_iter()
+                .filter_map(|r| Some((r, values.value_of(r)?, after.value_of(r)?)));
+            crate::journal::record("doctor", changes).map_err(Error::Journal)?;
+            let rechecked = diagnose(&after, &context);
+            for (finding, recheck) in findings.iter_mut().zip(rechecked) {
+                if finding.status == Status::Problem && recheck.status == Status::Ok {
+                    // The clock is not journaled, as writing the previous time back would only
+                    // put the device clock off again.
+                    let suggestion = matches!(finding.fix, Some(Fix::SyncClock))
+                        .then(|| "not journaled, cannot be undone with `write --undo`".to_string());
+                    *finding = Finding { status: Status::Fixed, suggestion, ..recheck };
+                } else if finding.fix.is_some() {
+                    *finding = recheck;
+                }
+            }
+        }
+    }
+
+    let mut output = args.output.to_output().map_err(Error::CreateOutput)?;
+    output
+        .table_headers(vec!["Check", "Status", "Detail", "Suggestion"])
+        .map_err(Error::WriteOutput)?;
+    for finding in &findings {
+        let status = <&'static str>::from(finding.status);
+        output
+            .result(
+                || {
+                    vec![
+                        finding.check.to_string(),
+                        status.to_string(),
+                        finding.detail.clone(),
+                        finding.suggestion.clone().unwrap_or_default(),
+                    ]
+                },
+                || OutputSchema {
+                    check: finding.check,
+                    status,
+                    detail: &finding.detail,
+                    suggestion: finding.suggestion.as_deref(),
+                },
+            )
+            .map_err(Error::WriteOutput)?;
+    }
+    output.commit().map_err(Error::CommitOutput)?;
+    let problems = findings.iter().filter(|f| f.status == Status::Problem).count();
+    if problems != 0 {
+        return Err(Error::ProblemsFound(problems));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registers::{DESCRIPTIONS, NAMES};
+
+    const CONTEXT: Context = Context {
+        device_id: 1,
+        baudrate: 115200,
+        max_clock_drift: jiff::SignedDuration::from_mins(2),
+    };
+
+    fn device(overrides: &[(&str, u16)]) -> ModbusDeviceValues {
+        let now = jiff::Zoned::now().datetime();
+        let clock = [now.year(), now.month().into(), now.day().into()]
+            .into_iter()
+            .chain([now.hour(), now.minute(), now.second()].map(i16::from))
+            .map(|v| v as u16);
+        let healthy = CLOCK_REGISTERS.into_iter().zip(clock).chain([
+            ("LOCKED_USER", 1),
+            ("LOCKED_FILTER", 1),
+            ("LOCKED_WEEK_SCHEDULE", 1),
+            ("PASSWD_USER_LEVEL_REQUIRED", 0),
+            ("PASSWD_FILTER_REQUIRED", 0),
+            ("PASSWD_WEEK_SCHEDULE_REQUIRED", 0),
+            ("SYSTEM_UNIT_FLOW", 1),
+            ("SYSTEM_UNIT_PRESSURE", 0),
+            ("SYSTEM_UNIT_TEMPERATURE", 0),
+            ("SUW_REQUIRED", 0),
+            ("USER_SAFE_CONFIG_VALID", 1),
+            ("COMM_MODBUS_ADDRESS", 1),
+            ("COMM_MODBUS_BAUD_RATE", 10),
+            ("COMM_MODBUS_PARITY", 0),
+        ]);
+        let mut values = ModbusDeviceValues::new();
+        for (name, value) in healthy.chain(overrides.iter().copied()) {
+            values.set_value(register(name).address(), value);
+        }
+        values
+    }
+
+    fn finding<'a>(findings: &'a [Finding], check: &str) -> &'a Finding {
+        findings.iter().find(|f| f.check == check).expect("check is made")
+    }
+
+    #[test]
+    fn healthy() {
+        let findings = diagnose(&device(&[]), &CONTEXT);
+        for finding in &findings {
+            assert_eq!(finding.status, Status::Ok, "{}: {}", finding.check, finding.detail);
+        }
+    }
+
+    #[test]
+    fn problems() {
+        let values = device(&[
+            ("SYSTEM_UNIT_TEMPERATURE", 1),
+            ("LOCKED_FILTER", 0),
+            ("PASSWD_USER_LEVEL_REQUIRED", 1),
+            ("USER_SAFE_CONFIG_VALID", 0),
+            ("TIME_YEAR", 2001),
+            ("COMM_MODBUS_BAUD_RATE", 3),
+        ]);
+        let findings = diagnose(&values, &CONTEXT);
+        let temperature = finding(&findings, "temperature unit");
+        assert_eq!((temperature.status, temperature.detail.as_str()), (Status::Problem, "°F"));
+        assert!(matches!(temperature.fix, Some(Fix::Set("SYSTEM_UNIT_TEMPERATURE", 0))));
+        let lock = finding(&findings, "filter menu lock");
+        assert!(matches!(lock.fix, Some(Fix::Set("LOCKED_FILTER", 1))));
+        let password = finding(&findings, "home screen password");
+        assert!(matches!(password.fix, Some(Fix::Set("PASSWD_USER_LEVEL_REQUIRED", 0))));
+        let config = finding(&findings, "user safe config");
+        assert_eq!(config.status, Status::Problem);
+        assert!(config.fix.is_none(), "storing the safe config is left to the user");
+        assert!(matches!(finding(&findings, "clock").fix, Some(Fix::SyncClock)));
+        let baud = finding(&findings, "modbus baud rate");
+        assert_eq!(
+            (baud.status, baud.suggestion.as_deref()),
+            (Status::Problem, Some("connect with `--baudrate 9600`"))
+        );
+        assert_eq!(findings.iter().filter(|f| f.status == Status::Problem).count(), 6);
+    }
+
+    #[test]
+    fn unreadable_values() {
+        let findings = diagnose(&ModbusDeviceValues::new(), &CONTEXT);
+        for finding in &findings {
+            assert_eq!(finding.status, Status::Unknown, "{}", finding.check);
+        }
+    }
+
+    #[test]
+    fn locks_are_one_when_unlocked() {
+        for (name, _) in LOCKS {
+            let index = NAMES.iter().position(|n| *n == name).expect("lock register is known");
+            let description = DESCRIPTIONS[index];
+            assert!(
+                description.contains("menu locked, 1=") && description.ends_with(" not locked")
+            );
+        }
+    }
+}
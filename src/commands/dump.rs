@@ -0,0 +1,77 @@
+//! Save the device's settings into a snapshot file.
+//!
+//! Only the registers holding persistent settings are saved, leaving out live readings, alarm
+//! state and triggers, so that the snapshot can be fed back into `restore` as is.
+
+use crate::connection::{self, Connection};
+use crate::modbus_device_cache::{ModbusDeviceValues, RegisterBitmask};
+use crate::registers::RegisterIndex;
+use crate::snapshot::{self, Entry, Snapshot};
+use std::path::PathBuf;
+
+/// Save the values of all writable settings into a snapshot file.
+#[derive(clap::Parser)]
+pub struct Args {
+    /// Where to write the snapshot to. Printed to the terminal if not specified.
+    #[arg(long, short = 'o')]
+    output: Option<PathBuf>,
+    /// The snapshot format. Inferred from the output file extension by default.
+    #[arg(long, short = 'f', value_enum)]
+    format: Option<snapshot::Format>,
+    #[clap(flatten)]
+    connection: connection::Args,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("could establish client connection with the device")]
+    EstablishClient(#[source] crate::connection::Error),
+    #[error("communication with the device failed")]
+    Communicate(#[source] crate::connection::Error),
+    #[error("could not save the snapshot")]
+    Save(#[source] crate::snapshot::Error),
+}
+
+/// Whether the register holds a persistent setting, as opposed to a live reading or a trigger for
+/// a one-off action.
+///
+/// Only the settings are worth saving and restoring. Writing the rest back would, for instance,
+/// clear alarms, reset the filter timer, turn on manual overrides or change the clock.
+pub(crate) fn is_setting(register: RegisterIndex) -> bool {
+    let name = register.name();
+    register.mode().is_writable()
+        && !register.is_action()
+        && !name.starts_with("SENSOR_")
+        && !name.starts_with("MANUAL_OVERRIDE_")
+        && !name.ends_with("_CLEAR_ALARM")
+}
+
+/// Read the values of all the settings from the device.
+pub(crate) async fn take(connection: &Connection) -> Result<Snapshot, crate::connection::Error> {
+    let registers = RegisterIndex::all().filter(|r| is_setting(*r)).collect::<Vec<_>>();
+    let mut mask = RegisterBitmask::new();
+    for register in &registers {
+        mask.set(register.address());
+    }
+    let mut values = ModbusDeviceValues::new();
+    values.read_registers(connection, &mask).await?;
+    let entries = registers
+        .into_iter()
+        .filter_map(|register| {
+            let value = values.value_of(register);
+            if value.is_none() {
+                tracing::warn!(register = register.name(), "could not read, omitting");
+            }
+            Some(Entry::new(register, value?))
+        })
+        .collect();
+    Ok(Snapshot::new(entries))
+}
+
+#[tokio::main(flavor = "current_thread")]
+pub async fn run(args: Args) -> Result<(), Error> {
+    let format = args.format.unwrap_or_else(|| snapshot::Format::from_path(args.output.as_deref()));
+    let connection = Connection::new(args.connection).await.map_err(Error::EstablishClient)?;
+    let snapshot = take(&connection).await.map_err(Error::Communicate)?;
+    snapshot.save(args.output.as_deref(), format).map_err(Error::Save)
+}
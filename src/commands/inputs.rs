@@ -0,0 +1,403 @@
+//! Inspecting and reassigning the universal (`ui1`..`ui6`) and digital (`di1`, `di2`) inputs.
+//!
+//! Functions are referred to by the same names as in the MQTT interface (e.g. `fire-alarm`), or
+//! by their raw register value. Only some of the universal input functions are known, so any other
+//! value has to be written with `--force`.
+
+use super::common::{check_critical, register, write};
+use crate::connection::{self, Connection};
+use crate::homie::input_node::{
+    DigitalInputConnection, DigitalInputPolarity, UniversalInputFunction,
+};
+use crate::modbus_device_cache::{ModbusDeviceValues, RegisterBitmask};
+use crate::output;
+use crate::registers::RegisterIndex;
+use strum::VariantArray as _;
+
+/// Show and reassign the universal and digital inputs.
+#[derive(clap::Parser)]
+pub struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Show the configuration and the live values of all inputs.
+    Show {
+        #[clap(flatten)]
+        connection: connection::Args,
+        #[clap(flatten)]
+        output: output::Args,
+    },
+    /// Assign a function to an input.
+    Assign {
+        /// The input to reassign, `ui1`..`ui6` or `di1`, `di2`.
+        #[arg(value_parser = parse_input)]
+        input: Input,
+        /// The function to assign, e.g. `co2-sensor` or `fire-alarm`, or a raw register value.
+        function: String,
+        /// The polarity of a digital input.
+        #[arg(long, value_parser = parse_polarity)]
+        polarity: Option<DigitalInputPolarity>,
+        /// Assign unknown or duplicate functions and reconfigure the fire alarm input.
+        #[arg(long)]
+        force: bool,
+        #[clap(flatten)]
+        connection: connection::Args,
+        #[clap(flatten)]
+        output: output::Args,
+    },
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("could establish client connection with the device")]
+    EstablishClient(#[source] crate::connection::Error),
+    #[error("communication with the device failed")]
+    Communicate(#[source] crate::connection::Error),
+    #[error("could not read the configuration of {0}")]
+    Unreadable(Input),
+    #[error("`{1}` is not a known function for {0}, use `--force` to assign it anyway")]
+    UnknownFunction(Input, String),
+    #[error("`--polarity` only applies to digital inputs")]
+    PolarityNotApplicable,
+    #[error("{1} is already assigned to {0}, use `--force` to assign it to both")]
+    AlreadyAssigned(Input, String),
+    #[error("this {0}, use `--force` if this is intended")]
+    Critical(&'static str),
+    #[error(transparent)]
+    Write(super::common::WriteError),
+    #[error("device did not apply the new value of {0}")]
+    NotApplied(&'static str),
+    #[error("could not record the changes in the journal")]
+    Journal(#[source] crate::journal::Error),
+    #[error(transparent)]
+    CreateOutput(crate::output::Error),
+    #[error(transparent)]
+    WriteOutput(crate::output::Error),
+    #[error(transparent)]
+    CommitOutput(crate::output::Error),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Input {
+    Universal(u8),
+    Digital(u8),
+}
+
+const INPUTS: [Input; 8] = [
+    Input::Universal(1),
+    Input::Universal(2),
+    Input::Universal(3),
+    Input::Universal(4),
+    Input::Universal(5),
+    Input::Universal(6),
+    Input::Digital(1),
+    Input::Digital(2),
+];
+
+impl std::fmt::Display for Input {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Universal(n) => write!(f, "ui{n}"),
+            Self::Digital(n) => write!(f, "di{n}"),
+        }
+    }
+}
+
+impl Input {
+    /// The register configuring the mode (universal) or polarity (digital) of the input.
+    fn mode_register(self) -> RegisterIndex {
+        match self {
+            Self::Universal(n) => register(&format!("UI_{n}_MODE")),
+            Self::Digital(n) => register(&format!("DI_CFG_POLARITY_{n}")),
+        }
+    }
+
+    /// The register configuring the function of the input.
+    fn function_register(self) -> RegisterIndex {
+        match self {
+            Self::Universal(n) => register(&format!("UI_{n}_FUNCTION")),
+            Self::Digital(n) => register(&format!("DI_CONNECTION_{n}")),
+        }
+    }
+
+    /// The register with the raw signal at the input.
+    fn signal_register(self) -> RegisterIndex {
+        match self {
+            Self::Universal(n) => register(&format!("INPUT_ANALOG_UI_{n}")),
+            Self::Digital(n) => register(&format!("INPUT_DIGITAL_DI_{n}")),
+        }
+    }
+
+    fn describe_mode(self, value: u16) -> String {
+        match self {
+            Self::Universal(_) if value == 1 => "analog".to_string(),
+            Self::Universal(_) => format!("unknown ({value})"),
+            Self::Digital(_) => DigitalInputPolarity::from_repr(value)
+                .map_or_else(|| format!("unknown ({value})"), |p| <&str>::from(p).to_string()),
+        }
+    }
+
+    fn describe_function(self, value: u16) -> String {
+        let name = match self {
+            Self::Universal(_) => UniversalInputFunction::from_repr(value).map(<&str>::from),
+            Self::Digital(_) => DigitalInputConnection::from_repr(value).map(<&str>::from),
+        };
+        name.map_or_else(|| format!("unknown ({value})"), str::to_string)
+    }
+
+    /// Parse a function name or a raw value, returning whether the function is a known one.
+    fn parse_function(self, function: &str) -> Option<(u16, bool)> {
+        let known = match self {
+            Self::Universal(_) => function.parse::<UniversalInputFunction>().map(|f| f as u16),
+            Self::Digital(_) => function.parse::<DigitalInputConnection>().map(|f| f as u16),
+        };
+        if let Ok(value) = known {
+            return Some((value, true));
+        }
+        let value = function.parse::<u16>().ok()?;
+        Some((value, self.describe_function(value) != format!("unknown ({value})")))
+    }
+}
+
+fn parse_input(s: &str) -> Result<Input, String> {
+    INPUTS
+        .into_iter()
+        .find(|input| input.to_string().eq_ignore_ascii_case(s))
+        .ok_or_else(|| "expected one of ui1..ui6, di1 or di2".to_string())
+}
+
+fn parse_polarity(s: &str) -> Result<DigitalInputPolarity, String> {
+    s.parse().map_err(|_| "expected normally-open or normally-closed".to_string())
+}
+
+fn sensor_registers(n: u8) -> [RegisterIndex; 2] {
+    [register(&format!("SENSOR_CO2S_{n}")), register(&format!("SENSOR_RHS_{n}"))]
+}
+
+async fn read_inputs(connection: &Connection) -> Result<ModbusDeviceValues, Error> {
+    let mut mask = RegisterBitmask::new();
+    for input in INPUTS {
+        mask.set(input.mode_register().address());
+        mask.set(input.function_register().address());
+        mask.set(input.signal_register().address());
+        if let Input::Universal(n) = input {
+            for sensor in sensor_registers(n) {
+                mask.set(sensor.address());
+            }
+        }
+    }
+    for sensor in DigitalInputConnection::VARIANTS.iter().filter_map(|c| c.sensor_register()) {
+        mask.set(register(sensor).address());
+    }
+    let mut values = ModbusDeviceValues::new();
+    values.read_registers(connection, &mask).await.map_err(Error::Communicate)?;
+    Ok(values)
+}
+
+#[derive(serde::Serialize)]
+struct OutputSchema {
+    input: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    function: Option<String>,
+    /// The voltage at a universal input in mV, or the state of a digital input.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signal: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    co2: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rh: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    active: Option<bool>,
+}
+
+fn print_inputs(values: &ModbusDeviceValues, output: output::Args) -> Result<(), Error> {
+    let value = |register| values.value_of(register).map(|v| v.into_inner());
+    let mut output = output.to_output().map_err(Error::CreateOutput)?;
+    let heads = vec!["Input", "Mode", "Function", "Signal", "Reading"];
+    output.table_headers(heads).map_err(Error::WriteOutput)?;
+    for input in INPUTS {
+        let mode = value(input.mode_register()).map(|v| input.describe_mode(v));
+        let function_value = value(input.function_register());
+        let function = function_value.map(|v| input.describe_function(v));
+        let signal = value(input.signal_register());
+        let (mut co2, mut rh, mut active) = (None, None, None);
+        let (signal_text, reading) = match input {
+            Input::Universal(n) => {
+                let [co2_register, rh_register] = sensor_registers(n);
+                co2 = value(co2_register);
+                rh = value(rh_register);
+                let reading = [co2.map(|v| format!("CO₂ {v} ppm")), rh.map(|v| format!("RH {v}%"))];
+                (
+                    signal.map(|v| format!("{v} mV")),
+                    reading.into_iter().flatten().collect::<Vec<_>>(),
+                )
+            }
+            Input::Digital(_) => {
+                let sensor = function_value
+                    .and_then(DigitalInputConnection::from_repr)
+                    .and_then(|c| c.sensor_register());
+                active = sensor.and_then(|s| value(register(s))).map(|v| v != 0);
+                let reading = active.map(|a| if a { "active" } else { "inactive" }.to_string());
+                let signal = signal.map(|v| if v != 0 { "on" } else { "off" }.to_string());
+                (signal, reading.into_iter().collect())
+            }
+        };
+        output
+            .result(
+                || {
+                    vec![
+                        input.to_string(),
+                        mode.clone().unwrap_or_default(),
+                        function.clone().unwrap_or_default(),
+                        signal_text.clone().unwrap_or_default(),
+                        reading.join(", "),
+                    ]
+                },
+                || OutputSchema {
+                    input: input.to_string(),
+                    mode: mode.clone(),
+                    function: function.clone(),
+                    signal,
+                    co2,
+                    rh,
+                    active,
+                },
+            )
+            .map_err(Error::WriteOutput)?;
+    }
+    output.commit().map_err(Error::CommitOutput)
+}
+
+/// Check that the new function can be assigned to the input without `--force`.
+fn check_assignment(
+    values: &ModbusDeviceValues,
+    input: Input,
+    function: u16,
+    polarity: Option<DigitalInputPolarity>,
+) -> Result<(), Error> {
+    let value = |register| values.value_of(register).map(|v| v.into_inner());
+    let current = value(input.function_register()).ok_or(Error::Unreadable(input))?;
+    let unassigned =
+        matches!(input, Input::Digital(_)) && function == DigitalInputConnection::None as u16;
+    if !unassigned {
+        let same_kind = |other: &Input| {
+            std::mem::discriminant(other) == std::mem::discriminant(&input) && *other != input
+        };
+        if let Some(other) = INPUTS
+            .into_iter()
+            .filter(same_kind)
+            .find(|other| value(other.function_register()) == Some(function))
+        {
+            return Err(Error::AlreadyAssigned(other, input.describe_function(function)));
+        }
+    }
+    if let Input::Digital(_) = input {
+        let fire_alarm = DigitalInputConnection::FireAlarm as u16;
+        let polarity_changes =
+            polarity.is_some_and(|p| value(input.mode_register()) != Some(p as u16));
+        if current == fire_alarm && (function != fire_alarm || polarity_changes)
+            || function == fire_alarm && current != fire_alarm
+        {
+            let effect = input.function_register().critical_effect();
+            return Err(Error::Critical(effect.expect("digital inputs are critical")));
+        }
+    }
+    Ok(())
+}
+
+#[tokio::main(flavor = "current_thread")]
+pub async fn run(args: Args) -> Result<(), Error> {
+    match args.command {
+        Command::Show { connection, output } => {
+            let connection = Connection::new(connection).await.map_err(Error::EstablishClient)?;
+            print_inputs(&read_inputs(&connection).await?, output)
+        }
+        Command::Assign { input, function, polarity, force, connection, output } => {
+            if polarity.is_some() && matches!(input, Input::Universal(_)) {
+                return Err(Error::PolarityNotApplicable);
+            }
+            let function_value = match input.parse_function(&function) {
+                Some((value, true)) => value,
+                Some((value, false)) if force => value,
+                _ => return Err(Error::UnknownFunction(input, function)),
+            };
+            let connection = Connection::new(connection).await.map_err(Error::EstablishClient)?;
+            let values = read_inputs(&connection).await?;
+            if !force {
+                check_assignment(&values, input, function_value, polarity)?;
+            }
+            let mut writes = vec![(input.function_register(), function_value)];
+            if let Some(polarity) = polarity {
+                writes.push((input.mode_register(), polarity as u16));
+            }
+            if force {
+                for &(register, value) in &writes {
+                    if let Some(effect) =
+                        check_critical(register, force).map_err(Error::Critical)?
+                    {
+                        let register = register.name();
+                        tracing::warn!(
+                            register,
+                            value,
+                            effect,
+                            "safety-critical, will write anyway…!"
+                        );
+                    }
+                }
+            }
+            for &(register, value) in &writes {
+                write(&connection, register, value).await.map_err(Error::Write)?;
+            }
+            let before = values;
+            let values = read_inputs(&connection).await?;
+            for &(register, value) in &writes {
+                if values.value_of(register).map(|v| v.into_inner()) != Some(value) {
+                    return Err(Error::NotApplied(register.name()));
+                }
+            }
+            let changes = writes.into_iter().filter_map(|(register, _)| {
+                Some((register, before.value_of(register)?, values.value_of(register)?))
+            });
+            crate::journal::record("inputs", changes).map_err(Error::Journal)?;
+            print_inputs(&values, output)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inputs() {
+        assert_eq!(parse_input("UI3"), Ok(Input::Universal(3)));
+        assert_eq!(parse_input("di2"), Ok(Input::Digital(2)));
+        assert!(parse_input("ui7").is_err());
+        assert_eq!(Input::Universal(2).function_register().name(), "UI_2_FUNCTION");
+        assert_eq!(Input::Digital(1).function_register().name(), "DI_CONNECTION_1");
+        assert_eq!(Input::Digital(1).mode_register().name(), "DI_CFG_POLARITY_1");
+    }
+
+    #[test]
+    fn functions() {
+        let fire_alarm = DigitalInputConnection::FireAlarm as u16;
+        let digital = Input::Digital(1);
+        assert_eq!(digital.parse_function("fire-alarm"), Some((fire_alarm, true)));
+        assert_eq!(digital.parse_function(&fire_alarm.to_string()), Some((fire_alarm, true)));
+        assert_eq!(digital.describe_function(fire_alarm), "fire-alarm");
+        assert_eq!(digital.parse_function("9999"), Some((9999, false)));
+        assert_eq!(digital.describe_function(9999), "unknown (9999)");
+        assert_eq!(digital.parse_function("no-such-function"), None);
+        let universal = Input::Universal(1);
+        let co2 = UniversalInputFunction::Co2Sensor as u16;
+        assert_eq!(universal.parse_function("co2-sensor"), Some((co2, true)));
+        assert_eq!(universal.describe_function(co2), "co2-sensor");
+        // Digital input functions do not apply to the universal inputs.
+        assert_eq!(universal.parse_function("fire-alarm"), None);
+    }
+}
@@ -0,0 +1,178 @@
+//! Inspecting and lifting the locks that make the device ignore changes to its settings.
+//!
+//! Access from the Modbus interface is unlocked by writing the administrator password into
+//! `PASSWD_PC_SETTINGS`, encoded the same way as `PASSWD_ADMIN`: each of the four digits takes up
+//! four bits, with the first digit in the most significant bits. `PASSWD_PC_UNLOCKED` then
+//! indicates whether the password was accepted.
+
+use super::common::{register, write};
+use crate::connection::{self, Connection};
+use crate::modbus_device_cache::{ModbusDeviceValues, RegisterBitmask};
+use crate::output;
+
+/// Inspect and lift the device locks.
+#[derive(clap::Parser)]
+pub struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Show which of the locks are in effect.
+    Status {
+        #[clap(flatten)]
+        connection: connection::Args,
+        #[clap(flatten)]
+        output: output::Args,
+    },
+    /// Unlock changes to the settings with the administrator password.
+    Unlock {
+        /// The four digit administrator password.
+        pin: Pin,
+        #[clap(flatten)]
+        connection: connection::Args,
+        #[clap(flatten)]
+        output: output::Args,
+    },
+    /// Lock changes to the settings again.
+    Relock {
+        #[clap(flatten)]
+        connection: connection::Args,
+        #[clap(flatten)]
+        output: output::Args,
+    },
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("could establish client connection with the device")]
+    EstablishClient(#[source] crate::connection::Error),
+    #[error("communication with the device failed")]
+    Communicate(#[source] crate::connection::Error),
+    #[error(transparent)]
+    Write(super::common::WriteError),
+    #[error("device did not accept the password")]
+    NotUnlocked,
+    #[error(transparent)]
+    CreateOutput(crate::output::Error),
+    #[error(transparent)]
+    WriteOutput(crate::output::Error),
+    #[error(transparent)]
+    CommitOutput(crate::output::Error),
+}
+
+/// A four digit password, encoded as one digit per nibble.
+#[derive(Clone, Copy)]
+pub(crate) struct Pin(u16);
+
+impl std::str::FromStr for Pin {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 4 || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return Err("the password must consist of exactly four digits".into());
+        }
+        Ok(Self(s.bytes().fold(0, |pin, digit| pin << 4 | u16::from(digit - b'0'))))
+    }
+}
+
+/// The lock state registers, all of which are `0` when locked.
+const LOCKS: [(&str, &str); 4] = [
+    ("user menu", "LOCKED_USER"),
+    ("filter menu", "LOCKED_FILTER"),
+    ("week schedule menu", "LOCKED_WEEK_SCHEDULE"),
+    ("settings over modbus", "PASSWD_PC_UNLOCKED"),
+];
+
+async fn read_states(connection: &Connection) -> Result<ModbusDeviceValues, Error> {
+    let mut mask = RegisterBitmask::new();
+    for (_, name) in LOCKS {
+        mask.set(register(name).address());
+    }
+    let mut values = ModbusDeviceValues::new();
+    values.read_registers(connection, &mask).await.map_err(Error::Communicate)?;
+    Ok(values)
+}
+
+/// Unlock changes to the settings and verify that the device accepted the password.
+pub(crate) async fn unlock(connection: &Connection, pin: Pin) -> Result<(), Error> {
+    write(connection, register("PASSWD_PC_SETTINGS"), pin.0).await.map_err(Error::Write)?;
+    let values = read_states(connection).await?;
+    match values.value_of(register("PASSWD_PC_UNLOCKED")).map(|v| v.into_inner()) {
+        Some(1) => Ok(()),
+        _ => Err(Error::NotUnlocked),
+    }
+}
+
+/// Lock changes to the settings again.
+pub(crate) async fn relock(connection: &Connection) -> Result<(), Error> {
+    write(connection, register("PASSWD_PC_SETTINGS"), 0).await.map_err(Error::Write)
+}
+
+#[derive(serde::Serialize)]
+struct OutputSchema {
+    lock: &'static str,
+    register: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    locked: Option<bool>,
+}
+
+fn print_states(values: &ModbusDeviceValues, output: output::Args) -> Result<(), Error> {
+    let mut output = output.to_output().map_err(Error::CreateOutput)?;
+    output.table_headers(vec!["Lock", "Register", "State"]).map_err(Error::WriteOutput)?;
+    for (lock, name) in LOCKS {
+        let register = register(name);
+        let locked = values.value_of(register).map(|v| v.into_inner() == 0);
+        let state = match locked {
+            Some(true) => "locked",
+            Some(false) => "unlocked",
+            None => "unknown",
+        };
+        output
+            .result(
+                || vec![lock.to_string(), register.name().to_string(), state.to_string()],
+                || OutputSchema { lock, register: register.name(), locked },
+            )
+            .map_err(Error::WriteOutput)?;
+    }
+    output.commit().map_err(Error::CommitOutput)
+}
+
+#[tokio::main(flavor = "current_thread")]
+pub async fn run(args: Args) -> Result<(), Error> {
+    match args.command {
+        Command::Status { connection, output } => {
+            let connection = Connection::new(connection).await.map_err(Error::EstablishClient)?;
+            print_states(&read_states(&connection).await?, output)
+        }
+        Command::Unlock { pin, connection, output } => {
+            let connection = Connection::new(connection).await.map_err(Error::EstablishClient)?;
+            unlock(&connection, pin).await?;
+            print_states(&read_states(&connection).await?, output)
+        }
+        Command::Relock { connection, output } => {
+            let connection = Connection::new(connection).await.map_err(Error::EstablishClient)?;
+            relock(&connection).await?;
+            print_states(&read_states(&connection).await?, output)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pin(s: &str) -> Result<u16, String> {
+        s.parse::<Pin>().map(|pin| pin.0)
+    }
+
+    #[test]
+    fn pins() {
+        assert_eq!(pin("1234"), Ok(0x1234));
+        assert_eq!(pin("0000"), Ok(0));
+        assert_eq!(pin("9099"), Ok(0x9099));
+        for invalid in ["", "123", "12345", "12a4", "+123", " 123", "١٢٣٤"] {
+            assert!(pin(invalid).is_err(), "{invalid}");
+        }
+    }
+}
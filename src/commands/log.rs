@@ -0,0 +1,307 @@
+//! Long-running sampling of register values into log files.
+//!
+//! Each sample becomes one row (CSV) or one object (JSONL) holding the timestamp and the values
+//! of all the selected registers. The file is always opened for appending, so restarting the
+//! command continues the existing log. Once rotation is due, the current file is renamed after
+//! the time it was started and a fresh file is created in its place.
+
+use crate::connection::{self, Connection};
+use crate::modbus_device_cache::{ModbusDeviceValues, SetBitsIterator};
+use crate::registers::{DataType, RegisterIndex, Value};
+use std::io::{BufRead as _, Write as _};
+use std::path::{Path, PathBuf};
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum Format {
+    Csv,
+    Jsonl,
+}
+
+impl Format {
+    /// Pick the format based on the file extension, defaulting to CSV.
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("jsonl" | "json") => Format::Jsonl,
+            _ => Format::Csv,
+        }
+    }
+}
+
+/// Sample registers at a fixed interval and append their values to a log file.
+#[derive(clap::Parser)]
+pub struct Args {
+    /// Registers to log, in any of the forms accepted by `read`.
+    #[arg(required = true)]
+    registers: Vec<String>,
+    /// The file to append the samples to.
+    #[arg(long, short = 'o')]
+    output: PathBuf,
+    /// The log format. Inferred from the output file extension by default.
+    #[arg(long, short = 'f', value_enum)]
+    format: Option<Format>,
+    /// The time between the samples.
+    #[arg(long, short = 'n', default_value = "1m")]
+    interval: humantime::Duration,
+    /// Start a new file once the current one grows to this size (e.g. `10M`.)
+    #[arg(long, value_parser = parse_size)]
+    max_size: Option<u64>,
+    /// Start a new file every day.
+    #[arg(long)]
+    daily: bool,
+    /// Give up on a sample that takes longer than this and reconnect to the device.
+    #[arg(long, default_value = "30s")]
+    sample_timeout: humantime::Duration,
+    #[clap(flatten)]
+    requests: super::read::RequestArgs,
+    #[clap(flatten)]
+    connection: connection::Args,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("could not select the registers to log")]
+    Select(#[source] super::read::Error),
+    #[error("could establish client connection with the device")]
+    EstablishClient(#[source] crate::connection::Error),
+    #[error("could not open the log file at {1:?}")]
+    Open(#[source] std::io::Error, PathBuf),
+    #[error("could not write to the log file at {1:?}")]
+    Write(#[source] std::io::Error, PathBuf),
+    #[error("could not rotate the log file {1:?} to {2:?}")]
+    Rotate(#[source] std::io::Error, PathBuf, PathBuf),
+    #[error("could not serialize the sample to JSON")]
+    SerializeJson(#[source] serde_json::Error),
+}
+
+fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let s = s.strip_suffix("iB").or_else(|| s.strip_suffix('B')).unwrap_or(s);
+    let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(digits);
+    let number = number.parse::<u64>().map_err(|e| e.to_string())?;
+    let multiplier = match unit {
+        "" => 1,
+        "k" | "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => return Err(format!("unknown size unit `{unit}`")),
+    };
+    number.checked_mul(multiplier).ok_or_else(|| "size is too large".into())
+}
+
+/// A single row of the log.
+struct Sample<'a> {
+    timestamp: String,
+    columns: &'a [(String, DataType)],
+    values: Vec<Option<Value>>,
+}
+
+impl serde::Serialize for Sample<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap as _;
+        let mut map = serializer.serialize_map(Some(self.values.len() + 1))?;
+        map.serialize_entry("timestamp", &self.timestamp)?;
+        for ((column, _), value) in self.columns.iter().zip(&self.values) {
+            map.serialize_entry(column, value)?;
+        }
+        map.end()
+    }
+}
+
+struct LogFile {
+    path: PathBuf,
+    format: Format,
+    header: Vec<String>,
+    file: std::fs::File,
+    size: u64,
+    started: jiff::Zoned,
+}
+
+impl LogFile {
+    fn open(path: PathBuf, format: Format, header: Vec<String>) -> Result<Self, Error> {
+        let error_path = path.clone();
+        let open_error = move |e| Error::Open(e, error_path.clone());
+        let file = std::fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .read(true)
+            .open(&path)
+            .map_err(&open_error)?;
+        let metadata = file.metadata().map_err(&open_error)?;
+        let started = match metadata.modified() {
+            Ok(time) if metadata.len() > 0 => jiff::Timestamp::try_from(time)
+                .map(|t| t.to_zoned(jiff::tz::TimeZone::system()))
+                .unwrap_or_else(|_| jiff::Zoned::now()),
+            _ => jiff::Zoned::now(),
+        };
+        let mut log = Self { path, format, header, file, size: metadata.len(), started };
+        if log.size == 0 {
+            log.write_header()?;
+        } else if !log.has_matching_header().map_err(&open_error)? {
+            // Appending rows with different columns would make the existing log unreadable.
+            tracing::info!(path = ?log.path, "registers changed since the log was started");
+            log.rotate()?;
+        }
+        Ok(log)
+    }
+
+    fn has_matching_header(&self) -> Result<bool, std::io::Error> {
+        let Format::Csv = self.format else { return Ok(true) };
+        let mut expected = vec![];
+        crate::output::write_csv_record(&mut expected, &self.header)?;
+        let mut actual = String::new();
+        std::io::BufReader::new(&self.file).read_line(&mut actual)?;
+        Ok(actual.as_bytes() == expected)
+    }
+
+    fn write_header(&mut self) -> Result<(), Error> {
+        match self.format {
+            Format::Csv => {
+                let mut buffer = vec![];
+                crate::output::write_csv_record(&mut buffer, &self.header)
+                    .map_err(|e| Error::Write(e, self.path.clone()))?;
+                self.write(&buffer)
+            }
+            Format::Jsonl => Ok(()),
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.file.write_all(data).map_err(|e| Error::Write(e, self.path.clone()))?;
+        self.size += data.len() as u64;
+        Ok(())
+    }
+
+    /// Rename the current file after the time it was started and begin a new one.
+    fn rotate(&mut self) -> Result<(), Error> {
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let extension = self.path.extension().map(|e| format!(".{}", e.to_string_lossy()));
+        let started = self.started.strftime("%Y%m%dT%H%M%S");
+        let mut target;
+        let mut attempt = 0;
+        loop {
+            let suffix = if attempt == 0 { String::new() } else { format!("-{attempt}") };
+            let name = format!("{stem}-{started}{suffix}{}", extension.as_deref().unwrap_or(""));
+            target = self.path.with_file_name(name);
+            if !target.exists() {
+                break;
+            }
+            attempt += 1;
+        }
+        std::fs::rename(&self.path, &target)
+            .map_err(|e| Error::Rotate(e, self.path.clone(), target.clone()))?;
+        tracing::info!(path = ?self.path, rotated = ?target, "rotated the log file");
+        *self = Self::open(self.path.clone(), self.format, std::mem::take(&mut self.header))?;
+        Ok(())
+    }
+
+    fn append(&mut self, sample: &Sample) -> Result<(), Error> {
+        let mut buffer = vec![];
+        match self.format {
+            Format::Csv => {
+                let mut row = vec![sample.timestamp.clone()];
+                row.extend(
+                    sample.values.iter().map(|v| v.map(|v| v.to_string()).unwrap_or_default()),
+                );
+                crate::output::write_csv_record(&mut buffer, &row)
+                    .map_err(|e| Error::Write(e, self.path.clone()))?;
+            }
+            Format::Jsonl => {
+                serde_json::to_writer(&mut buffer, sample).map_err(Error::SerializeJson)?;
+                buffer.push(b'\n');
+            }
+        }
+        self.write(&buffer)
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+pub async fn run(args: Args) -> Result<(), Error> {
+    let mask = super::read::parse_mask(&args.registers).map_err(Error::Select)?;
+    let addresses = SetBitsIterator::new(&mask).collect::<Vec<_>>();
+    let columns = addresses
+        .iter()
+        .map(|&address| match RegisterIndex::from_address(address) {
+            Some(index) => (index.name().to_string(), index.data_type()),
+            None => (address.to_string(), DataType::U16),
+        })
+        .collect::<Vec<_>>();
+    let format = args.format.unwrap_or_else(|| Format::from_path(&args.output));
+    let header = std::iter::once("timestamp".to_string())
+        .chain(columns.iter().map(|(name, _)| name.clone()))
+        .collect();
+    let mut log = LogFile::open(args.output, format, header)?;
+
+    let mut connection =
+        Connection::new(args.connection.clone()).await.map_err(Error::EstablishClient)?;
+    let options = args.requests.options();
+    let mut interval = tokio::time::interval(*args.interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        let mut values = ModbusDeviceValues::new();
+        let sampled = tokio::time::timeout(
+            *args.sample_timeout,
+            values.read_registers_with(&connection, &mask, &options),
+        );
+        let failed = match sampled.await {
+            Ok(Ok(_)) => false,
+            Ok(Err(e)) => {
+                tracing::warn!(
+                    message = "sampling failed, will reconnect",
+                    error = (&e as &dyn std::error::Error)
+                );
+                true
+            }
+            Err(_) => {
+                tracing::warn!("sampling timed out, will reconnect");
+                true
+            }
+        };
+        if failed {
+            connection.worker.abort();
+            connection =
+                Connection::new(args.connection.clone()).await.map_err(Error::EstablishClient)?;
+            continue;
+        }
+        let now = jiff::Zoned::now();
+        let rotate_daily = args.daily && now.date() != log.started.date();
+        let rotate_size = args.max_size.is_some_and(|max| log.size >= max);
+        if rotate_daily || rotate_size {
+            log.rotate()?;
+        }
+        let values = addresses
+            .iter()
+            .zip(&columns)
+            .map(|(&address, (_, data_type))| {
+                values.value_of_address(address).map(|word| data_type.from_word(word))
+            })
+            .collect();
+        log.append(&Sample { timestamp: now.timestamp().to_string(), columns: &columns, values })?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size(" 10k "), Ok(10 << 10));
+        assert_eq!(parse_size("10KiB"), Ok(10 << 10));
+        assert_eq!(parse_size("5MB"), Ok(5 << 20));
+        assert_eq!(parse_size("2G"), Ok(2 << 30));
+        assert_eq!(parse_size("7B"), Ok(7));
+    }
+
+    #[test]
+    fn invalid_sizes() {
+        assert!(parse_size("").is_err());
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("10T").is_err());
+        assert!(parse_size("1.5M").is_err());
+        assert!(parse_size("-1").is_err());
+        assert!(parse_size("18446744073709551615k").is_err());
+    }
+}
@@ -0,0 +1,278 @@
+//! Overriding a fan output or a sensor input for a limited amount of time.
+//!
+//! An override consists of a mode register (`0` for AUTO, `1` for MANUAL) and a register with the
+//! value to use while in the MANUAL mode. Overrides that are left behind keep the device from
+//! operating normally, so this command stays connected for the duration of the override and sets
+//! the mode back to AUTO when the time runs out or the command is interrupted.
+//!
+//! Only the overrides whose mode and value registers are both known are offered. The
+//! `MANUAL_OVERRIDE_INPUT_*` registers for the raw AI, DI and UI inputs have no mode registers in
+//! the register table, while the RPM, UI6, bypass damper, piggyback pressure, RH, CO2 and PDM modes
+//! have no value registers, so none of these can be overridden with this command.
+
+use super::common::{check_range, register, write};
+use crate::connection::{self, Connection};
+use crate::modbus_device_cache::{ModbusDeviceValues, RegisterBitmask};
+use crate::registers::{RegisterIndex, Value};
+use std::time::Duration;
+
+/// Override a fan output or sensor input for a limited time.
+#[derive(clap::Parser)]
+pub struct Args {
+    /// The output or input to override, e.g. `saf` or `oat`.
+    name: String,
+    /// The value to use, e.g. `60%` or `21.5°C`.
+    #[arg(allow_hyphen_values = true)]
+    value: String,
+    /// How long to keep the override in effect.
+    #[arg(long = "for", default_value = "10m")]
+    duration: humantime::Duration,
+    /// Use a value outside of the range accepted by the device.
+    #[arg(long)]
+    force: bool,
+    #[clap(flatten)]
+    connection: connection::Args,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("`{0}` is not a known override, expected one of: {1}")]
+    OverrideNotFound(String, String),
+    #[error("could not parse `{0}` as a value for the {1} override")]
+    ParseValue(String, &'static str, #[source] crate::registers::ParseValueError),
+    #[error(
+        "value {1} for the {0} override is outside of the range {2}, use `--force` to use it anyway"
+    )]
+    OutOfRange(&'static str, Value, String),
+    #[error("could establish client connection with the device")]
+    EstablishClient(#[source] crate::connection::Error),
+    #[error("communication with the device failed")]
+    Communicate(#[source] crate::connection::Error),
+    #[error(transparent)]
+    Write(super::common::WriteError),
+    #[error("could not listen for the interrupt signal")]
+    Signal(#[source] std::io::Error),
+}
+
+struct Override {
+    name: &'static str,
+    /// The register selecting between AUTO (`0`) and MANUAL (`1`).
+    mode: &'static str,
+    /// The register with the value used in the MANUAL mode.
+    value: &'static str,
+    unit: &'static str,
+}
+
+const OVERRIDES: [Override; 12] = [
+    Override {
+        name: "saf",
+        mode: "MANUAL_OVERRIDE_OUTPUT_SAF",
+        value: "MANUAL_OVERRIDE_OUTPUT_SAF_VALUE",
+        unit: "%",
+    },
+    Override {
+        name: "eaf",
+        mode: "MANUAL_OVERRIDE_OUTPUT_EAF",
+        value: "MANUAL_OVERRIDE_OUTPUT_EAF_VALUE",
+        unit: "%",
+    },
+    Override {
+        name: "safc",
+        mode: "MANUAL_OVERRIDE_F_INPUT_UI_SAFC_MODE",
+        value: "MANUAL_OVERRIDE_F_INPUT_SAFC_VALUE",
+        unit: "%",
+    },
+    Override {
+        name: "eafc",
+        mode: "MANUAL_OVERRIDE_F_INPUT_UI_EAFC_MODE",
+        value: "MANUAL_OVERRIDE_F_INPUT_EAFC_VALUE",
+        unit: "%",
+    },
+    Override {
+        name: "oat",
+        mode: "MANUAL_OVERRIDE_F_INPUT_OAT_MODE",
+        value: "MANUAL_OVERRIDE_F_INPUT_OAT_VALUE",
+        unit: "°C",
+    },
+    Override {
+        name: "sat",
+        mode: "MANUAL_OVERRIDE_F_INPUT_SAT_MODE",
+        value: "MANUAL_OVERRIDE_F_INPUT_SAT_VALUE",
+        unit: "°C",
+    },
+    Override {
+        name: "oht",
+        mode: "MANUAL_OVERRIDE_F_INPUT_OHT_MODE",
+        value: "MANUAL_OVERRIDE_F_INPUT_OHT_VALUE",
+        unit: "°C",
+    },
+    Override {
+        name: "fpt",
+        mode: "MANUAL_OVERRIDE_F_INPUT_FPT_MODE",
+        value: "MANUAL_OVERRIDE_F_INPUT_FPT_VALUE",
+        unit: "°C",
+    },
+    Override {
+        name: "rat",
+        mode: "MANUAL_OVERRIDE_F_INPUT_RAT_MODE",
+        value: "MANUAL_OVERRIDE_F_INPUT_RAT_VALUE",
+        unit: "°C",
+    },
+    Override {
+        name: "eat",
+        mode: "MANUAL_OVERRIDE_F_INPUT_EAT_MODE",
+        value: "MANUAL_OVERRIDE_F_INPUT_EAT_VALUE",
+        unit: "°C",
+    },
+    Override {
+        name: "ect",
+        mode: "MANUAL_OVERRIDE_F_INPUT_ECT_MODE",
+        value: "MANUAL_OVERRIDE_F_INPUT_ECT_VALUE",
+        unit: "°C",
+    },
+    Override {
+        name: "eft",
+        mode: "MANUAL_OVERRIDE_F_INPUT_EFT_MODE",
+        value: "MANUAL_OVERRIDE_F_INPUT_EFT_VALUE",
+        unit: "°C",
+    },
+];
+
+const AUTO: u16 = 0;
+const MANUAL: u16 = 1;
+
+/// How often to check on the override while it is in effect.
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+fn parse_value(over: &Override, value: &str, force: bool) -> Result<Value, Error> {
+    let register = register(over.value);
+    let number = value.trim().strip_suffix(over.unit).unwrap_or(value).trim();
+    let number =
+        if over.unit == "°C" { number.strip_suffix('C').unwrap_or(number) } else { number };
+    let parsed = register
+        .data_type()
+        .parse_string(number)
+        .map_err(|e| Error::ParseValue(value.to_string(), over.name, e))?;
+    if !force {
+        check_range(register, parsed)
+            .map_err(|range| Error::OutOfRange(over.name, parsed, range))?;
+    }
+    Ok(parsed)
+}
+
+async fn read_mode(connection: &Connection, mode: RegisterIndex) -> Result<Option<u16>, Error> {
+    let mut mask = RegisterBitmask::new();
+    mask.set(mode.address());
+    let mut values = ModbusDeviceValues::new();
+    values.read_registers(connection, &mask).await.map_err(Error::Communicate)?;
+    Ok(values.value_of(mode).map(|v| v.into_inner()))
+}
+
+/// Put the override into effect and wait until it runs out or is ended by somebody else.
+async fn apply_and_hold(
+    connection: &Connection,
+    over: &Override,
+    value: Value,
+    duration: Duration,
+) -> Result<(), Error> {
+    write(connection, register(over.value), value.into_inner()).await.map_err(Error::Write)?;
+    write(connection, register(over.mode), MANUAL).await.map_err(Error::Write)?;
+    let until = jiff::Zoned::now()
+        .checked_add(jiff::SignedDuration::try_from(duration).unwrap_or_default())
+        .map(|t| t.strftime("%H:%M:%S").to_string())
+        .unwrap_or_default();
+    eprintln!(
+        "Overriding {} with {value}{} until {until}, press Ctrl-C to end it earlier",
+        over.name, over.unit
+    );
+    let mode = register(over.mode);
+    let deadline = tokio::time::Instant::now() + duration;
+    loop {
+        let next_check = std::cmp::min(deadline, tokio::time::Instant::now() + CHECK_INTERVAL);
+        tokio::time::sleep_until(next_check).await;
+        if tokio::time::Instant::now() >= deadline {
+            eprintln!("Time is up, ending the {} override", over.name);
+            return Ok(());
+        }
+        // This also keeps the connection from being closed for inactivity.
+        if read_mode(connection, mode).await? == Some(AUTO) {
+            eprintln!("The {} override was ended by somebody else", over.name);
+            return Ok(());
+        }
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+pub async fn run(args: Args) -> Result<(), Error> {
+    let Some(over) = OVERRIDES.iter().find(|o| o.name.eq_ignore_ascii_case(&args.name)) else {
+        let names = OVERRIDES.iter().map(|o| o.name).collect::<Vec<_>>().join(", ");
+        return Err(Error::OverrideNotFound(args.name, names));
+    };
+    let value = parse_value(over, &args.value, args.force)?;
+    let connection = Connection::new(args.connection).await.map_err(Error::EstablishClient)?;
+    // Listening for Ctrl-C from before the first write on keeps an interrupt from terminating the
+    // process at any point where the override may already be in effect, including while AUTO is
+    // being restored below.
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    let held = tokio::select! {
+        biased;
+        result = &mut ctrl_c => {
+            eprintln!("Interrupted, ending the {} override", over.name);
+            result.map_err(Error::Signal)
+        }
+        result = apply_and_hold(&connection, over, value, *args.duration) => result,
+    };
+    // Restore AUTO on every path, including when putting the override into effect failed halfway.
+    let restored = write(&connection, register(over.mode), AUTO).await;
+    match (held, restored) {
+        (Err(e), Err(restore)) => {
+            eprintln!("Could not restore the {} override to AUTO: {restore}", over.name);
+            Err(e)
+        }
+        (held, restored) => {
+            restored.map_err(Error::Write)?;
+            eprintln!("Restored the {} override to AUTO", over.name);
+            held
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(name: &str) -> &'static Override {
+        OVERRIDES.iter().find(|o| o.name == name).unwrap()
+    }
+
+    #[test]
+    fn values() {
+        let saf = find("saf");
+        assert_eq!(parse_value(saf, "60%", false).unwrap(), Value::U16(60));
+        assert_eq!(parse_value(saf, " 60 % ", false).unwrap(), Value::U16(60));
+        assert_eq!(parse_value(saf, "60", false).unwrap(), Value::U16(60));
+        let oat = find("oat");
+        assert_eq!(parse_value(oat, "21.5°C", false).unwrap(), Value::Celsius(215));
+        assert_eq!(parse_value(oat, "-5C", false).unwrap(), Value::Celsius(-50));
+        assert!(matches!(parse_value(oat, "warm", false), Err(Error::ParseValue(..))));
+    }
+
+    #[test]
+    fn ranges() {
+        let saf = find("saf");
+        assert!(matches!(
+            parse_value(saf, "101%", false),
+            Err(Error::OutOfRange("saf", Value::U16(101), range)) if range == "0..=100"
+        ));
+        assert_eq!(parse_value(saf, "101%", true).unwrap(), Value::U16(101));
+    }
+
+    #[test]
+    fn registers_are_known() {
+        for over in &OVERRIDES {
+            register(over.mode);
+            register(over.value);
+        }
+    }
+}
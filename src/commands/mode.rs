@@ -0,0 +1,228 @@
+//! Showing and switching the user mode.
+//!
+//! Switching is a request to the device through `USERMODE_HMI_CHANGE_REQUEST`, the same way the
+//! control panel does it. The device may refuse or override the request (e.g. while an input
+//! forces another mode), so the command waits until the device reports the requested mode before
+//! declaring success. Temporary modes run for the duration stored in their own register, which
+//! `--for` updates before the request is made.
+
+use super::common::{check_range, register, write};
+use crate::connection::{self, Connection};
+use crate::homie::mode_node::CurrentMode;
+use crate::homie::value::{PropertyValue as _, RemainingTimeValue};
+use crate::modbus_device_cache::{ModbusDeviceValues, RegisterBitmask};
+use crate::output;
+use crate::registers::{RegisterIndex, Value};
+use std::time::Duration;
+
+/// Show the current user mode, or switch to another one.
+#[derive(clap::Parser)]
+pub struct Args {
+    /// The mode to switch to: auto, manual, crowded, refresh, fireplace, away or holiday.
+    ///
+    /// The current mode and its remaining time are shown when not specified.
+    #[arg(value_parser = parse_mode)]
+    mode: Option<CurrentMode>,
+    /// How long the mode should last (e.g. `12h`.)
+    ///
+    /// This is stored as the duration for the mode, so it will also apply the next time the mode
+    /// is activated some other way.
+    #[arg(long = "for", requires = "mode")]
+    duration: Option<humantime::Duration>,
+    /// How long to wait for the device to confirm the mode change.
+    #[arg(long, default_value = "15s")]
+    confirm_timeout: humantime::Duration,
+    #[clap(flatten)]
+    connection: connection::Args,
+    #[clap(flatten)]
+    output: output::Args,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("mode {0} does not have a duration")]
+    NoDuration(&'static str),
+    #[error("duration for mode {0} must be a whole number of {1}")]
+    DurationNotWhole(&'static str, &'static str),
+    #[error("mode {0} can last {1} {2}")]
+    DurationOutOfRange(&'static str, String, &'static str),
+    #[error("could establish client connection with the device")]
+    EstablishClient(#[source] crate::connection::Error),
+    #[error("communication with the device failed")]
+    Communicate(#[source] crate::connection::Error),
+    #[error(transparent)]
+    Write(super::common::WriteError),
+    #[error("could not read the current mode from the device")]
+    Incomplete,
+    #[error("device did not switch to mode {0} in time (current mode is {1})")]
+    NotConfirmed(&'static str, String),
+    #[error("could not record the changes in the journal")]
+    Journal(#[source] crate::journal::Error),
+    #[error(transparent)]
+    CreateOutput(crate::output::Error),
+    #[error(transparent)]
+    WriteOutput(crate::output::Error),
+    #[error(transparent)]
+    CommitOutput(crate::output::Error),
+}
+
+fn parse_mode(s: &str) -> Result<CurrentMode, String> {
+    let mode = s.parse::<CurrentMode>().map_err(|_| format!("`{s}` is not a known mode"))?;
+    if mode.request().is_none() {
+        return Err(format!("mode `{s}` is activated by the inputs and cannot be requested"));
+    }
+    Ok(mode)
+}
+
+fn mode_name(mode: CurrentMode) -> &'static str {
+    mode.into()
+}
+
+#[derive(serde::Serialize)]
+struct OutputSchema {
+    mode: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    remaining: Option<String>,
+}
+
+async fn read_mode(connection: &Connection) -> Result<ModbusDeviceValues, Error> {
+    let mut mask = RegisterBitmask::new();
+    for name in ["USERMODE_REMAINING_TIME_L", "USERMODE_REMAINING_TIME_H", "USERMODE_MODE"] {
+        mask.set(register(name).address());
+    }
+    let mut values = ModbusDeviceValues::new();
+    values.read_registers(connection, &mask).await.map_err(Error::Communicate)?;
+    Ok(values)
+}
+
+fn current_mode(values: &ModbusDeviceValues) -> Result<Result<CurrentMode, Value>, Error> {
+    let value = values.value_of(register("USERMODE_MODE")).ok_or(Error::Incomplete)?;
+    Ok(CurrentMode::try_from(value).map_err(|()| value))
+}
+
+/// Convert the requested duration into the value for the mode's duration register.
+fn duration_value(mode: CurrentMode, duration: Duration) -> Result<(RegisterIndex, u16), Error> {
+    let name = mode_name(mode);
+    let request = mode.request().expect("only requestable modes are parsed");
+    let (register, unit) = request.duration_register().ok_or(Error::NoDuration(name))?;
+    let (unit_seconds, unit_name) = match unit {
+        jiff::Unit::Day => (24 * 60 * 60, "days"),
+        jiff::Unit::Hour => (60 * 60, "hours"),
+        jiff::Unit::Minute => (60, "minutes"),
+        _ => unreachable!("mode durations are in days, hours or minutes"),
+    };
+    if duration.subsec_nanos() != 0 || !duration.as_secs().is_multiple_of(unit_seconds) {
+        return Err(Error::DurationNotWhole(name, unit_name));
+    }
+    let count = u16::try_from(duration.as_secs() / unit_seconds).unwrap_or(u16::MAX);
+    check_range(register, Value::U16(count))
+        .map_err(|range| Error::DurationOutOfRange(name, range, unit_name))?;
+    Ok((register, count))
+}
+
+#[tokio::main(flavor = "current_thread")]
+pub async fn run(args: Args) -> Result<(), Error> {
+    let duration = match (args.mode, args.duration) {
+        (Some(mode), Some(duration)) => Some(duration_value(mode, *duration)?),
+        _ => None,
+    };
+    let connection = Connection::new(args.connection).await.map_err(Error::EstablishClient)?;
+    if let Some(mode) = args.mode {
+        if let Some((register, value)) = duration {
+            let mut mask = RegisterBitmask::new();
+            mask.set(register.address());
+            let mut before = ModbusDeviceValues::new();
+            before.read_registers(&connection, &mask).await.map_err(Error::Communicate)?;
+            tracing::info!(register = register.name(), value, "setting the mode duration");
+            write(&connection, register, value).await.map_err(Error::Write)?;
+            let after = register.data_type().from_word(value);
+            let changes = before.value_of(register).map(|before| (register, before, after));
+            crate::journal::record("mode", changes).map_err(Error::Journal)?;
+        }
+        let request = mode.request().expect("only requestable modes are parsed");
+        tracing::info!(mode = mode_name(mode), "requesting the mode");
+        let change_request = register("USERMODE_HMI_CHANGE_REQUEST");
+        write(&connection, change_request, request as u16).await.map_err(Error::Write)?;
+        let deadline = tokio::time::Instant::now() + *args.confirm_timeout;
+        loop {
+            let current = current_mode(&read_mode(&connection).await?)?;
+            if current == Ok(mode) {
+                break;
+            }
+            if tokio::time::Instant::now() >= deadline {
+                let current = match current {
+                    Ok(current) => mode_name(current).to_string(),
+                    Err(value) => value.to_string(),
+                };
+                return Err(Error::NotConfirmed(mode_name(mode), current));
+            }
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+    }
+
+    let values = read_mode(&connection).await?;
+    let mode = match current_mode(&values)? {
+        Ok(mode) => mode_name(mode).to_string(),
+        Err(value) => value.to_string(),
+    };
+    let remaining = values
+        .value_of(register("USERMODE_REMAINING_TIME_L"))
+        .zip(values.value_of(register("USERMODE_REMAINING_TIME_H")))
+        .filter(|(l, h)| l.into_inner() != 0 || h.into_inner() != 0)
+        .and_then(|(l, h)| RemainingTimeValue::new(l, h).ok())
+        .map(|remaining| remaining.value());
+    let mut output = args.output.to_output().map_err(Error::CreateOutput)?;
+    output.table_headers(vec!["Mode", "Remaining"]).map_err(Error::WriteOutput)?;
+    output
+        .result(
+            || vec![mode.clone(), remaining.clone().unwrap_or_default()],
+            || OutputSchema { mode: mode.clone(), remaining: remaining.clone() },
+        )
+        .map_err(Error::WriteOutput)?;
+    output.commit().map_err(Error::CommitOutput)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+
+    #[test]
+    fn durations() {
+        let value = |mode, duration| {
+            let (register, value) = duration_value(mode, duration).unwrap();
+            (register.name(), value)
+        };
+        assert_eq!(value(CurrentMode::Away, 12 * HOUR), ("USERMODE_AWAY_TIME", 12));
+        assert_eq!(value(CurrentMode::Holiday, 48 * HOUR), ("USERMODE_HOLIDAY_TIME", 2));
+        assert_eq!(value(CurrentMode::Fireplace, HOUR), ("USERMODE_FIREPLACE_TIME", 60));
+    }
+
+    #[test]
+    fn invalid_durations() {
+        assert!(matches!(
+            duration_value(CurrentMode::Away, HOUR + Duration::from_secs(60)),
+            Err(Error::DurationNotWhole("away", "hours"))
+        ));
+        assert!(matches!(
+            duration_value(CurrentMode::Away, Duration::from_millis(500)),
+            Err(Error::DurationNotWhole(..))
+        ));
+        let Err(Error::DurationOutOfRange("away", range, "hours")) =
+            duration_value(CurrentMode::Away, 73 * HOUR)
+        else {
+            panic!("73 hours is too long to be away");
+        };
+        assert_eq!(range, "1..=72");
+        assert!(matches!(
+            duration_value(CurrentMode::Crowded, Duration::ZERO),
+            Err(Error::DurationOutOfRange(..))
+        ));
+        assert!(matches!(
+            duration_value(CurrentMode::Refresh, Duration::from_secs(u64::MAX / 60 * 60)),
+            Err(Error::DurationOutOfRange(..))
+        ));
+        assert!(matches!(duration_value(CurrentMode::Auto, HOUR), Err(Error::NoDuration(_))));
+    }
+}
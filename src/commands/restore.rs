@@ -0,0 +1,214 @@
+//! Write a snapshot produced by `dump` back into the device.
+//!
+//! Every entry is validated before anything is written: it must name a setting and hold a value
+//! within the register's documented range. Only the registers whose current value differs are
+//! written, and all of them are read back afterwards to confirm the device took the new values.
+
+use super::common::{check_critical, check_range};
+use crate::connection::{self, Connection};
+use crate::modbus::{Operation, ResponseKind};
+use crate::modbus_device_cache::{ModbusDeviceValues, RegisterBitmask};
+use crate::output;
+use crate::registers::{RegisterIndex, Value};
+use crate::snapshot::{self, Snapshot};
+use std::path::PathBuf;
+
+/// Write the settings saved with `dump` back into the device.
+#[derive(clap::Parser)]
+pub struct Args {
+    /// The snapshot file produced by `dump`.
+    snapshot: PathBuf,
+    /// The snapshot format. Inferred from the file extension by default.
+    #[arg(long, value_enum)]
+    snapshot_format: Option<snapshot::Format>,
+    /// Only show what would change, do not write anything.
+    #[arg(long)]
+    dry_run: bool,
+    /// Restore safety-critical settings as well, such as the Modbus communication settings.
+    #[arg(long)]
+    force: bool,
+    #[clap(flatten)]
+    connection: connection::Args,
+    #[clap(flatten)]
+    output: output::Args,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("could not load the snapshot")]
+    Load(#[source] crate::snapshot::Error),
+    #[error("snapshot entry is not valid")]
+    InvalidEntry(#[source] crate::snapshot::Error),
+    #[error("register {0} is not a setting that can be restored")]
+    NotSetting(&'static str),
+    #[error("value {1} for register {0} is out of the allowed range {2}")]
+    OutOfRange(&'static str, Value, String),
+    #[error("restoring {0} {1}, use `--force` if this is intended")]
+    Critical(&'static str, &'static str),
+    #[error("could establish client connection with the device")]
+    EstablishClient(#[source] crate::connection::Error),
+    #[error("communication with the device failed")]
+    Communicate(#[source] crate::connection::Error),
+    #[error("{0} registers could not be restored")]
+    Incomplete(usize),
+    #[error("could not record the changes in the journal")]
+    Journal(#[source] crate::journal::Error),
+    #[error(transparent)]
+    CreateOutput(crate::output::Error),
+    #[error(transparent)]
+    WriteOutput(crate::output::Error),
+    #[error(transparent)]
+    CommitOutput(crate::output::Error),
+}
+
+#[derive(serde::Serialize, Clone, Copy, PartialEq, Eq, strum::Display)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+enum Outcome {
+    Unchanged,
+    WouldChange,
+    Restored,
+    Mismatch,
+    Exception,
+    UnexpectedResponse,
+}
+
+#[derive(serde::Serialize)]
+struct OutputSchema {
+    address: u16,
+    name: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    current: Option<Value>,
+    snapshot: Value,
+    outcome: Outcome,
+}
+
+/// Order in which the registers are written.
+///
+/// Locks and passwords are written after all the other settings so that they do not get in the
+/// way of restoring those. Modbus communication settings go last as changing them is likely to
+/// cut the tool off from the device.
+fn write_order(register: RegisterIndex) -> (u8, u16) {
+    let name = register.name();
+    let group = if name.starts_with("COMM_MODBUS_") {
+        2
+    } else if name.starts_with("LOCKED_") || name.starts_with("PASSWD_") {
+        1
+    } else {
+        0
+    };
+    (group, register.address())
+}
+
+#[tokio::main(flavor = "current_thread")]
+pub async fn run(args: Args) -> Result<(), Error> {
+    let format =
+        args.snapshot_format.unwrap_or_else(|| snapshot::Format::from_path(Some(&args.snapshot)));
+    let snapshot = Snapshot::load(&args.snapshot, format).map_err(Error::Load)?;
+    let mut entries = vec![];
+    let mut mask = RegisterBitmask::new();
+    for entry in &snapshot.registers {
+        let register = entry.register().map_err(Error::InvalidEntry)?;
+        if !super::dump::is_setting(register) {
+            return Err(Error::NotSetting(register.name()));
+        }
+        let value = entry.parse_value(register).map_err(Error::InvalidEntry)?;
+        check_range(register, value)
+            .map_err(|range| Error::OutOfRange(register.name(), value, range))?;
+        mask.set(register.address());
+        entries.push((register, value));
+    }
+    entries.sort_by_key(|(register, _)| write_order(*register));
+
+    let mut output = args.output.to_output().map_err(Error::CreateOutput)?;
+    let heads = vec!["Address", "Name", "Current", "Snapshot", "Outcome"];
+    output.table_headers(heads).map_err(Error::WriteOutput)?;
+    let connection = Connection::new(args.connection).await.map_err(Error::EstablishClient)?;
+    let mut current = ModbusDeviceValues::new();
+    current.read_registers(&connection, &mask).await.map_err(Error::Communicate)?;
+    if !args.dry_run {
+        for &(register, value) in &entries {
+            if current.value_of(register) != Some(value) {
+                let effect = check_critical(register, args.force)
+                    .map_err(|effect| Error::Critical(register.name(), effect))?;
+                if let Some(effect) = effect {
+                    let register = register.name();
+                    tracing::warn!(register, %value, effect, "safety-critical, will restore anyway…!");
+                }
+            }
+        }
+    }
+    let mut outcomes = Vec::with_capacity(entries.len());
+    for &(register, value) in &entries {
+        let before = current.value_of(register);
+        let outcome = if before == Some(value) {
+            Outcome::Unchanged
+        } else if args.dry_run {
+            Outcome::WouldChange
+        } else {
+            let address = register.address();
+            tracing::info!(register = register.name(), ?before, %value, "restoring");
+            let values = vec![value.into_inner()];
+            let operation = Operation::SetHoldings { address, values };
+            let response = connection.send_retrying(operation).await.map_err(Error::Communicate)?;
+            match response.kind {
+                ResponseKind::ErrorCode(code) => {
+                    tracing::warn!(address, exception = code, "device rejected the value");
+                    Outcome::Exception
+                }
+                ResponseKind::SetHoldings { .. } => Outcome::Restored,
+                ResponseKind::GetHoldings { .. } => {
+                    tracing::warn!(address, "unexpected response to a set command");
+                    Outcome::UnexpectedResponse
+                }
+            }
+        };
+        outcomes.push((register, value, before, outcome));
+    }
+
+    if !args.dry_run {
+        let mut verify = ModbusDeviceValues::new();
+        verify.read_registers(&connection, &mask).await.map_err(Error::Communicate)?;
+        for (register, value, _, outcome) in &mut outcomes {
+            if *outcome != Outcome::Exception && verify.value_of(*register) != Some(*value) {
+                *outcome = Outcome::Mismatch;
+            }
+        }
+        let changes = outcomes.iter().filter_map(|&(register, value, before, outcome)| {
+            (outcome == Outcome::Restored).then_some((register, before?, value))
+        });
+        crate::journal::record("restore", changes).map_err(Error::Journal)?;
+    }
+
+    let mut failures = 0;
+    for (register, value, before, outcome) in outcomes {
+        if matches!(outcome, Outcome::Mismatch | Outcome::Exception | Outcome::UnexpectedResponse) {
+            failures += 1;
+        }
+        output
+            .result(
+                || {
+                    vec![
+                        register.address().to_string(),
+                        register.name().to_string(),
+                        before.map(|v| v.to_string()).unwrap_or_default(),
+                        value.to_string(),
+                        outcome.to_string(),
+                    ]
+                },
+                || OutputSchema {
+                    address: register.address(),
+                    name: register.name(),
+                    current: before,
+                    snapshot: value,
+                    outcome,
+                },
+            )
+            .map_err(Error::WriteOutput)?;
+    }
+    output.commit().map_err(Error::CommitOutput)?;
+    if failures > 0 {
+        return Err(Error::Incomplete(failures));
+    }
+    Ok(())
+}
@@ -0,0 +1,251 @@
+//! Sweep the device's address space in search of registers that are not documented.
+//!
+//! The device responds with an "illegal data address" exception (code 2) to any read that covers
+//! an address it does not know about. Addresses are read in chunks and any chunk that produces an
+//! exception gets split in halves until the responding addresses are isolated.
+
+use crate::connection::{self, Connection};
+use crate::modbus::{self, Operation, ResponseKind};
+use crate::output;
+use crate::registers::RegisterIndex;
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// Scan an address range for registers that respond to reads.
+#[derive(clap::Parser)]
+pub struct Args {
+    /// The first address to scan.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    start: u16,
+    /// The address at which to stop scanning (exclusive).
+    #[arg(long, default_value_t = crate::registers::ADDRESS_INDICES.len() as u16)]
+    end: u16,
+    /// The number of registers to request at once.
+    ///
+    /// Larger chunks make sparse areas quicker to sweep, but require more requests to isolate the
+    /// responding registers in the dense areas.
+    #[arg(
+        long,
+        default_value_t = 32,
+        value_parser = clap::value_parser!(u16).range(1..=i64::from(modbus::MAX_SAFE_READ_COUNT))
+    )]
+    chunk_size: u16,
+    /// Also output the responding registers that are already known to this tool.
+    #[arg(long)]
+    include_known: bool,
+    /// After the sweep, read the responding registers this many more times to classify their
+    /// behaviour.
+    #[arg(long, default_value_t = 0)]
+    samples: usize,
+    /// The time to wait between the samples.
+    #[arg(long, default_value = "5s")]
+    interval: humantime::Duration,
+    #[clap(flatten)]
+    connection: connection::Args,
+    #[clap(flatten)]
+    output: output::Args,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("could establish client connection with the device")]
+    EstablishClient(#[source] crate::connection::Error),
+    #[error("address range {0}..{1} is empty")]
+    RangeEmpty(u16, u16),
+    #[error("communication with the device failed")]
+    Communicate(#[source] crate::connection::Error),
+    #[error(transparent)]
+    CreateOutput(crate::output::Error),
+    #[error(transparent)]
+    WriteOutput(crate::output::Error),
+    #[error(transparent)]
+    CommitOutput(crate::output::Error),
+}
+
+#[derive(serde::Serialize, Clone, Copy, PartialEq, Eq, strum::Display)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+enum Behaviour {
+    /// The value did not change throughout the observation period.
+    Static,
+    /// The value only ever increased.
+    Counter,
+    /// The value moved around in both directions.
+    Volatile,
+}
+
+/// Running observations of a single register.
+struct Observation {
+    first: u16,
+    last: u16,
+    changes: usize,
+    decreased: bool,
+}
+
+impl Observation {
+    fn new(value: u16) -> Self {
+        Self { first: value, last: value, changes: 0, decreased: false }
+    }
+
+    fn observe(&mut self, value: u16) {
+        if value != self.last {
+            self.changes += 1;
+            self.decreased |= value < self.last;
+        }
+        self.last = value;
+    }
+
+    fn behaviour(&self) -> Behaviour {
+        match (self.changes, self.decreased) {
+            (0, _) => Behaviour::Static,
+            (_, false) => Behaviour::Counter,
+            (_, true) => Behaviour::Volatile,
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct OutputSchema {
+    address: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'static str>,
+    value: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_value: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    behaviour: Option<Behaviour>,
+}
+
+#[tokio::main(flavor = "current_thread")]
+pub async fn run(args: Args) -> Result<(), Error> {
+    if args.end <= args.start {
+        return Err(Error::RangeEmpty(args.start, args.end));
+    }
+    let mut output = args.output.to_output().map_err(Error::CreateOutput)?;
+    let connection = Connection::new(args.connection).await.map_err(Error::EstablishClient)?;
+
+    // A stack of ranges that remain to be probed, with the lowest addresses at the top.
+    let mut pending = (args.start..args.end)
+        .step_by(usize::from(args.chunk_size))
+        .map(|start| start..args.end.min(start.saturating_add(args.chunk_size)))
+        .collect::<Vec<_>>();
+    pending.reverse();
+    let mut observations = BTreeMap::new();
+    while let Some(range) = pending.pop() {
+        let count = range.end - range.start;
+        let operation = Operation::GetHoldings { address: range.start, count };
+        let response = connection.send_retrying(operation).await.map_err(Error::Communicate)?;
+        match response.kind {
+            ResponseKind::GetHoldings { values } => {
+                tracing::debug!(start = range.start, count, "range responded");
+                let (words, _) = values.as_chunks::<2>();
+                for (address, word) in range.zip(words) {
+                    observations.insert(address, Observation::new(u16::from_be_bytes(*word)));
+                }
+            }
+            ResponseKind::ErrorCode(code) if count > 1 => {
+                tracing::debug!(start = range.start, count, code, "range raised an exception");
+                let middle = range.start + count / 2;
+                pending.push(middle..range.end);
+                pending.push(range.start..middle);
+            }
+            ResponseKind::ErrorCode(code) => {
+                tracing::trace!(address = range.start, code, "address does not respond");
+            }
+            ResponseKind::SetHoldings { .. } => {
+                tracing::warn!(start = range.start, count, "unexpected response to a get command");
+            }
+        }
+    }
+    tracing::info!(responding = observations.len(), "sweep complete");
+
+    if args.samples > 0 {
+        let runs = contiguous_runs(observations.keys().copied());
+        for sample in 0..args.samples {
+            tokio::time::sleep(*args.interval).await;
+            tracing::info!(sample, "sampling responding registers");
+            for run in &runs {
+                let count = run.end - run.start;
+                let operation = Operation::GetHoldings { address: run.start, count };
+                let response =
+                    connection.send_retrying(operation).await.map_err(Error::Communicate)?;
+                let ResponseKind::GetHoldings { values } = response.kind else {
+                    tracing::warn!(start = run.start, count, "previously responding range failed");
+                    continue;
+                };
+                let (words, _) = values.as_chunks::<2>();
+                for (address, word) in run.clone().zip(words) {
+                    if let Some(observation) = observations.get_mut(&address) {
+                        observation.observe(u16::from_be_bytes(*word));
+                    }
+                }
+            }
+        }
+    }
+
+    let heads = vec!["Address", "Name", "Value", "Last Value", "Behaviour"];
+    output.table_headers(heads).map_err(Error::WriteOutput)?;
+    for (&address, observation) in &observations {
+        let name = RegisterIndex::from_address(address).map(|r| r.name());
+        if name.is_some() && !args.include_known {
+            continue;
+        }
+        let sampled = args.samples > 0;
+        let last_value = sampled.then_some(observation.last);
+        let behaviour = sampled.then(|| observation.behaviour());
+        output
+            .result(
+                || {
+                    vec![
+                        address.to_string(),
+                        name.unwrap_or("???").to_string(),
+                        observation.first.to_string(),
+                        last_value.map(|v| v.to_string()).unwrap_or_default(),
+                        behaviour.map(|b| b.to_string()).unwrap_or_default(),
+                    ]
+                },
+                || OutputSchema { address, name, value: observation.first, last_value, behaviour },
+            )
+            .map_err(Error::WriteOutput)?;
+    }
+    output.commit().map_err(Error::CommitOutput)
+}
+
+/// Group sorted addresses into contiguous runs no longer than a single read can cover.
+fn contiguous_runs(addresses: impl Iterator<Item = u16>) -> Vec<Range<u16>> {
+    let mut runs: Vec<Range<u16>> = Vec::new();
+    for address in addresses {
+        match runs.last_mut() {
+            Some(run)
+                if run.end == address && run.end - run.start < modbus::MAX_SAFE_READ_COUNT =>
+            {
+                run.end += 1
+            }
+            _ => runs.push(address..address + 1),
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn behaviour(values: &[u16]) -> Behaviour {
+        let mut observation = Observation::new(values[0]);
+        for &value in &values[1..] {
+            observation.observe(value);
+        }
+        observation.behaviour()
+    }
+
+    #[test]
+    fn behaviours() {
+        assert!(behaviour(&[5]) == Behaviour::Static);
+        assert!(behaviour(&[5, 5, 5]) == Behaviour::Static);
+        assert!(behaviour(&[5, 6, 6, 9]) == Behaviour::Counter);
+        assert!(behaviour(&[5, 6, 5]) == Behaviour::Volatile);
+        // A value that returns to where it started has still moved around.
+        assert!(behaviour(&[5, 4, 5]) == Behaviour::Volatile);
+    }
+}
@@ -0,0 +1,522 @@
+//! Viewing and editing the week schedule.
+//!
+//! The schedule consists of two periods for each day of the week. Each period has a start and
+//! an end time spread over four registers (hour and minute each) and a separate enable flag.
+//! When a period is active, the unit runs with the scheduled fan level and temperature offset.
+
+use super::common::{check_critical, check_range, register};
+use crate::connection::{self, Connection};
+use crate::modbus::{Operation, ResponseKind};
+use crate::modbus_device_cache::{ModbusDeviceValues, RegisterBitmask};
+use crate::output;
+use crate::registers::{RegisterIndex, Value};
+use crate::snapshot::{self, Number};
+use std::path::{Path, PathBuf};
+
+/// View and edit the week schedule.
+#[derive(clap::Parser)]
+pub struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Print the week schedule as a timetable.
+    Show {
+        #[clap(flatten)]
+        connection: connection::Args,
+        #[clap(flatten)]
+        output: output::Args,
+    },
+    /// Change a period of the week schedule.
+    ///
+    /// For example `schedule set mon,tue 1 06:30-08:00` or `schedule set sun 2 off`.
+    Set {
+        /// The days to change, separated by commas (e.g. `mon,tue`.)
+        days: Days,
+        /// The period to change.
+        #[arg(value_parser = clap::value_parser!(u8).range(1..=2))]
+        period: u8,
+        /// The period as `HH:MM-HH:MM`, or `on`/`off` to only enable or disable it.
+        period_spec: PeriodSpec,
+        #[clap(flatten)]
+        connection: connection::Args,
+        #[clap(flatten)]
+        output: output::Args,
+    },
+    /// Save the whole week schedule into a file.
+    Export {
+        /// Where to write the schedule to. Printed to the terminal if not specified.
+        #[arg(long, short = 'o')]
+        output: Option<PathBuf>,
+        /// The file format. Inferred from the output file extension by default.
+        #[arg(long, short = 'f', value_enum)]
+        format: Option<snapshot::Format>,
+        #[clap(flatten)]
+        connection: connection::Args,
+    },
+    /// Replace the whole week schedule with the one from a file produced by `export`.
+    Import {
+        /// The schedule file.
+        schedule: PathBuf,
+        /// The file format. Inferred from the file extension by default.
+        #[arg(long, value_enum)]
+        schedule_format: Option<snapshot::Format>,
+        #[clap(flatten)]
+        connection: connection::Args,
+        #[clap(flatten)]
+        output: output::Args,
+    },
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("could not load the schedule")]
+    Load(#[source] crate::snapshot::Error),
+    #[error("could not save the schedule")]
+    Save(#[source] crate::snapshot::Error),
+    #[error("could not parse value {1} for register {0}")]
+    ParseValue(&'static str, Number, #[source] crate::registers::ParseValueError),
+    #[error("value {1} for register {0} is out of the allowed range {2}")]
+    OutOfRange(&'static str, Value, String),
+    #[error("period {1} on {0} starts and ends at the same time")]
+    EmptyPeriod(Weekday, usize),
+    #[error("could establish client connection with the device")]
+    EstablishClient(#[source] crate::connection::Error),
+    #[error("communication with the device failed")]
+    Communicate(#[source] crate::connection::Error),
+    #[error("could not read the week schedule from the device")]
+    Incomplete,
+    #[error("device rejected the write to registers starting at {0} with exception {1}")]
+    Rejected(u16, u8),
+    #[error("writing {0} {1}, use `write --force` if this is intended")]
+    Critical(&'static str, &'static str),
+    #[error("register {0} reads back as {1} rather than {2} after writing")]
+    Mismatch(&'static str, Value, Value),
+    #[error("could not record the changes in the journal")]
+    Journal(#[source] crate::journal::Error),
+    #[error(transparent)]
+    CreateOutput(crate::output::Error),
+    #[error(transparent)]
+    WriteOutput(crate::output::Error),
+    #[error(transparent)]
+    CommitOutput(crate::output::Error),
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Weekday {
+    #[value(alias = "mon")]
+    Monday,
+    #[value(alias = "tue")]
+    Tuesday,
+    #[value(alias = "wed")]
+    Wednesday,
+    #[value(alias = "thu")]
+    Thursday,
+    #[value(alias = "fri")]
+    Friday,
+    #[value(alias = "sat")]
+    Saturday,
+    #[value(alias = "sun")]
+    Sunday,
+}
+
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Monday,
+    Weekday::Tuesday,
+    Weekday::Wednesday,
+    Weekday::Thursday,
+    Weekday::Friday,
+    Weekday::Saturday,
+    Weekday::Sunday,
+];
+
+#[derive(Clone)]
+struct Days(Vec<Weekday>);
+
+impl std::str::FromStr for Days {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(|day| <Weekday as clap::ValueEnum>::from_str(day, true))
+            .collect::<Result<_, _>>()
+            .map(Days)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct TimeOfDay {
+    hour: u16,
+    minute: u16,
+}
+
+impl std::str::FromStr for TimeOfDay {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (hour, minute) = s.split_once(':').ok_or_else(|| format!("`{s}` is not HH:MM"))?;
+        let hour = hour.parse::<u16>().map_err(|e| format!("hour `{hour}`: {e}"))?;
+        let minute = minute.parse::<u16>().map_err(|e| format!("minute `{minute}`: {e}"))?;
+        if hour > 23 || minute > 59 {
+            return Err(format!("`{s}` is not a valid time of day"));
+        }
+        Ok(Self { hour, minute })
+    }
+}
+
+impl std::fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute)
+    }
+}
+
+impl serde::Serialize for TimeOfDay {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for TimeOfDay {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq)]
+struct Period {
+    enabled: bool,
+    start: TimeOfDay,
+    end: TimeOfDay,
+}
+
+impl std::fmt::Display for Period {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.enabled { write!(f, "{}-{}", self.start, self.end) } else { f.write_str("off") }
+    }
+}
+
+#[derive(Clone)]
+enum PeriodSpec {
+    Enable(bool),
+    Times(TimeOfDay, TimeOfDay),
+}
+
+impl std::str::FromStr for PeriodSpec {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "on" => Ok(PeriodSpec::Enable(true)),
+            "off" => Ok(PeriodSpec::Enable(false)),
+            _ => {
+                let (start, end) = s
+                    .split_once('-')
+                    .ok_or_else(|| format!("`{s}` is neither `on`, `off` nor HH:MM-HH:MM"))?;
+                Ok(PeriodSpec::Times(start.parse()?, end.parse()?))
+            }
+        }
+    }
+}
+
+/// The entire week schedule, as stored in the schedule files.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Week {
+    scheduled_fan_level: u16,
+    unscheduled_fan_level: u16,
+    scheduled_temperature_offset: Number,
+    unscheduled_temperature_offset: Number,
+    monday: [Period; 2],
+    tuesday: [Period; 2],
+    wednesday: [Period; 2],
+    thursday: [Period; 2],
+    friday: [Period; 2],
+    saturday: [Period; 2],
+    sunday: [Period; 2],
+}
+
+/// Registers making up a period, in the order start hour, start minute, end hour, end minute,
+/// enable flag.
+fn period_registers(day: Weekday, period: usize) -> [RegisterIndex; 5] {
+    let prefix = format!("WS_DAY{}_PRD{}", day as usize + 1, period + 1);
+    ["START_H", "START_M", "END_H", "END_M", "ENABLED"]
+        .map(|suffix| register(&format!("{prefix}_{suffix}")))
+}
+
+impl Week {
+    fn day(&self, day: Weekday) -> &[Period; 2] {
+        match day {
+            Weekday::Monday => &self.monday,
+            Weekday::Tuesday => &self.tuesday,
+            Weekday::Wednesday => &self.wednesday,
+            Weekday::Thursday => &self.thursday,
+            Weekday::Friday => &self.friday,
+            Weekday::Saturday => &self.saturday,
+            Weekday::Sunday => &self.sunday,
+        }
+    }
+
+    fn day_mut(&mut self, day: Weekday) -> &mut [Period; 2] {
+        match day {
+            Weekday::Monday => &mut self.monday,
+            Weekday::Tuesday => &mut self.tuesday,
+            Weekday::Wednesday => &mut self.wednesday,
+            Weekday::Thursday => &mut self.thursday,
+            Weekday::Friday => &mut self.friday,
+            Weekday::Saturday => &mut self.saturday,
+            Weekday::Sunday => &mut self.sunday,
+        }
+    }
+
+    fn registers() -> RegisterBitmask {
+        let mut mask = RegisterBitmask::new();
+        for index in RegisterIndex::all().filter(|r| r.name().starts_with("WS_")) {
+            mask.set(index.address());
+        }
+        mask
+    }
+
+    fn from_device(values: &ModbusDeviceValues) -> Result<Self, Error> {
+        let word = |index: RegisterIndex| values.value_of_address(index.address());
+        let value = |name| values.value_of(register(name)).ok_or(Error::Incomplete);
+        let period = |day, period| -> Result<Period, Error> {
+            let [start_h, start_m, end_h, end_m, enabled] = period_registers(day, period).map(word);
+            Ok(Period {
+                enabled: enabled.ok_or(Error::Incomplete)? != 0,
+                start: TimeOfDay {
+                    hour: start_h.ok_or(Error::Incomplete)?,
+                    minute: start_m.ok_or(Error::Incomplete)?,
+                },
+                end: TimeOfDay {
+                    hour: end_h.ok_or(Error::Incomplete)?,
+                    minute: end_m.ok_or(Error::Incomplete)?,
+                },
+            })
+        };
+        let day = |day| Ok::<_, Error>([period(day, 0)?, period(day, 1)?]);
+        Ok(Self {
+            scheduled_fan_level: value("WS_FAN_LEVEL_SCHEDULED")?.into_inner(),
+            unscheduled_fan_level: value("WS_FAN_LEVEL_UNSCHEDULED")?.into_inner(),
+            scheduled_temperature_offset: value("WS_T_OFFSET_ACTIVE")?.into(),
+            unscheduled_temperature_offset: value("WS_T_OFFSET_INACTIVE")?.into(),
+            monday: day(Weekday::Monday)?,
+            tuesday: day(Weekday::Tuesday)?,
+            wednesday: day(Weekday::Wednesday)?,
+            thursday: day(Weekday::Thursday)?,
+            friday: day(Weekday::Friday)?,
+            saturday: day(Weekday::Saturday)?,
+            sunday: day(Weekday::Sunday)?,
+        })
+    }
+
+    /// Validate the schedule and convert it into the register values to store in the device.
+    fn to_registers(&self) -> Result<Vec<(RegisterIndex, Value)>, Error> {
+        let mut result = vec![];
+        let mut push = |index: RegisterIndex, value: Number| -> Result<(), Error> {
+            let value = index
+                .data_type()
+                .parse_string(&value.to_string())
+                .map_err(|e| Error::ParseValue(index.name(), value, e))?;
+            check_range(index, value)
+                .map_err(|range| Error::OutOfRange(index.name(), value, range))?;
+            result.push((index, value));
+            Ok(())
+        };
+        let int = |v: u16| Number::Integer(v.into());
+        push(register("WS_T_OFFSET_ACTIVE"), self.scheduled_temperature_offset)?;
+        push(register("WS_T_OFFSET_INACTIVE"), self.unscheduled_temperature_offset)?;
+        push(register("WS_FAN_LEVEL_SCHEDULED"), int(self.scheduled_fan_level))?;
+        push(register("WS_FAN_LEVEL_UNSCHEDULED"), int(self.unscheduled_fan_level))?;
+        for day in WEEKDAYS {
+            for (index, period) in self.day(day).iter().enumerate() {
+                if period.enabled && period.start == period.end {
+                    return Err(Error::EmptyPeriod(day, index + 1));
+                }
+                let [start_h, start_m, end_h, end_m, enabled] = period_registers(day, index);
+                push(start_h, int(period.start.hour))?;
+                push(start_m, int(period.start.minute))?;
+                push(end_h, int(period.end.hour))?;
+                push(end_m, int(period.end.minute))?;
+                push(enabled, int(period.enabled.into()))?;
+            }
+        }
+        result.sort_by_key(|(index, _)| index.address());
+        Ok(result)
+    }
+}
+
+async fn read_week(connection: &Connection) -> Result<(ModbusDeviceValues, Week), Error> {
+    let mut values = ModbusDeviceValues::new();
+    values.read_registers(connection, &Week::registers()).await.map_err(Error::Communicate)?;
+    let week = Week::from_device(&values)?;
+    Ok((values, week))
+}
+
+/// Write the registers that differ from the `current` values.
+///
+/// Adjacent registers are written together with a single request. Unchanged registers between
+/// the changed ones are rewritten with their current value to avoid splitting the request.
+async fn write_week(
+    connection: &Connection,
+    current: &ModbusDeviceValues,
+    week: &Week,
+) -> Result<(), Error> {
+    let desired = week.to_registers()?;
+    let mut runs: Vec<(u16, Vec<u16>, bool)> = vec![];
+    for (index, value) in desired {
+        check_critical(index, false).map_err(|effect| Error::Critical(index.name(), effect))?;
+        let address = index.address();
+        let changed = current.value_of(index) != Some(value);
+        match runs.last_mut() {
+            Some((start, values, _)) if *start + values.len() as u16 == address => {
+                values.push(value.into_inner())
+            }
+            _ => runs.push((address, vec![value.into_inner()], false)),
+        }
+        runs.last_mut().expect("just pushed").2 |= changed;
+    }
+    for (address, mut values, changed) in runs {
+        if !changed {
+            continue;
+        }
+        // Trim the unchanged registers from either end of the run.
+        let unchanged = |(offset, word): &(usize, &u16)| {
+            current.value_of_address(address + *offset as u16) == Some(**word)
+        };
+        let first = values.iter().enumerate().position(|e| !unchanged(&e)).expect("changed");
+        let last = values.iter().enumerate().rposition(|e| !unchanged(&e)).expect("changed");
+        values.truncate(last + 1);
+        let values = values.split_off(first);
+        let address = address + first as u16;
+        tracing::debug!(address, count = values.len(), "writing week schedule registers");
+        let operation = Operation::SetHoldings { address, values };
+        let response = connection.send_retrying(operation).await.map_err(Error::Communicate)?;
+        if let ResponseKind::ErrorCode(code) = response.kind {
+            return Err(Error::Rejected(address, code));
+        }
+    }
+    Ok(())
+}
+
+/// Write the week into the device and verify it has been stored.
+async fn store_week(connection: &Connection, week: &Week) -> Result<Week, Error> {
+    let (current, _) = read_week(connection).await?;
+    write_week(connection, &current, week).await?;
+    let (stored, stored_week) = read_week(connection).await?;
+    for (index, value) in week.to_registers()? {
+        let actual = stored.value_of(index).ok_or(Error::Incomplete)?;
+        if actual != value {
+            return Err(Error::Mismatch(index.name(), actual, value));
+        }
+    }
+    let changes = week
+        .to_registers()?
+        .into_iter()
+        .filter_map(|(index, value)| Some((index, current.value_of(index)?, value)));
+    crate::journal::record("schedule", changes).map_err(Error::Journal)?;
+    Ok(stored_week)
+}
+
+#[derive(serde::Serialize)]
+struct OutputSchema<'a> {
+    day: String,
+    periods: &'a [Period; 2],
+}
+
+fn print_timetable(week: &Week, output: output::Args) -> Result<(), Error> {
+    let mut output = output.to_output().map_err(Error::CreateOutput)?;
+    let heads = vec!["Day", "Period 1", "Period 2"];
+    output.table_headers(heads).map_err(Error::WriteOutput)?;
+    for day in WEEKDAYS {
+        let periods = week.day(day);
+        output
+            .result(
+                || vec![day.to_string(), periods[0].to_string(), periods[1].to_string()],
+                || OutputSchema { day: day.to_string(), periods },
+            )
+            .map_err(Error::WriteOutput)?;
+    }
+    output.commit().map_err(Error::CommitOutput)
+}
+
+fn load(path: &Path, format: Option<snapshot::Format>) -> Result<Week, Error> {
+    let format = format.unwrap_or_else(|| snapshot::Format::from_path(Some(path)));
+    snapshot::load(path, format).map_err(Error::Load)
+}
+
+fn save(week: &Week, path: Option<&Path>, format: Option<snapshot::Format>) -> Result<(), Error> {
+    let format = format.unwrap_or_else(|| snapshot::Format::from_path(path));
+    snapshot::save(week, path, format).map_err(Error::Save)
+}
+
+#[tokio::main(flavor = "current_thread")]
+pub async fn run(args: Args) -> Result<(), Error> {
+    match args.command {
+        Command::Show { connection, output } => {
+            let connection = Connection::new(connection).await.map_err(Error::EstablishClient)?;
+            let (_, week) = read_week(&connection).await?;
+            print_timetable(&week, output)
+        }
+        Command::Set { days, period, period_spec, connection, output } => {
+            let connection = Connection::new(connection).await.map_err(Error::EstablishClient)?;
+            let (_, mut week) = read_week(&connection).await?;
+            for day in days.0 {
+                let period = &mut week.day_mut(day)[usize::from(period) - 1];
+                match period_spec {
+                    PeriodSpec::Enable(enabled) => period.enabled = enabled,
+                    PeriodSpec::Times(start, end) => {
+                        *period = Period { enabled: true, start, end };
+                    }
+                }
+            }
+            let week = store_week(&connection, &week).await?;
+            print_timetable(&week, output)
+        }
+        Command::Export { output, format, connection } => {
+            let connection = Connection::new(connection).await.map_err(Error::EstablishClient)?;
+            let (_, week) = read_week(&connection).await?;
+            save(&week, output.as_deref(), format)
+        }
+        Command::Import { schedule, schedule_format, connection, output } => {
+            let week = load(&schedule, schedule_format)?;
+            // Validate before touching the device at all.
+            week.to_registers()?;
+            let connection = Connection::new(connection).await.map_err(Error::EstablishClient)?;
+            let week = store_week(&connection, &week).await?;
+            print_timetable(&week, output)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const fn time(hour: u16, minute: u16) -> TimeOfDay {
+        TimeOfDay { hour, minute }
+    }
+
+    #[test]
+    fn times_of_day() {
+        assert_eq!("07:30".parse(), Ok(time(7, 30)));
+        assert_eq!("0:05".parse(), Ok(time(0, 5)));
+        assert_eq!("23:59".parse(), Ok(time(23, 59)));
+        assert_eq!(time(7, 5).to_string(), "07:05");
+        for invalid in ["", "7", "24:00", "12:60", "12:-1", "aa:00", "12:00:00"] {
+            assert!(invalid.parse::<TimeOfDay>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn period_specs() {
+        assert!(matches!("on".parse(), Ok(PeriodSpec::Enable(true))));
+        assert!(matches!("off".parse(), Ok(PeriodSpec::Enable(false))));
+        assert!(matches!(
+            "06:00-08:30".parse(),
+            Ok(PeriodSpec::Times(start, end)) if start == time(6, 0) && end == time(8, 30)
+        ));
+        for invalid in ["", "ON", "06:00", "06:00-", "06:00-25:00"] {
+            assert!(invalid.parse::<PeriodSpec>().is_err(), "{invalid}");
+        }
+    }
+}
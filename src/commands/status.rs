@@ -0,0 +1,169 @@
+//! Showing the device state as the properties of the MQTT interface.
+//!
+//! Many settings and readings are spread over several registers or are encoded in a way that is
+//! not obvious from the raw register values (e.g. the remaining filter time is split into a low
+//! and a high word.) This command decodes them the same way as the `mqtt` command does, but
+//! without needing an MQTT broker.
+
+use crate::connection::{self, Connection};
+use crate::homie;
+use crate::homie::node::PropertyEntry;
+use crate::modbus_device_cache::{ModbusDeviceValues, RegisterBitmask};
+use crate::output;
+use homie5::HomieID;
+
+/// Show the decoded properties of the specified nodes (e.g. `filter`.)
+///
+/// The nodes and properties are the same as those exposed by the `mqtt` command. All nodes are
+/// shown if none are specified.
+#[derive(clap::Parser)]
+pub struct Args {
+    nodes: Vec<HomieID>,
+    #[clap(flatten)]
+    connection: connection::Args,
+    #[clap(flatten)]
+    output: output::Args,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("`{0}` is not a known node, expected one of: {1}")]
+    NodeNotFound(HomieID, String),
+    #[error("could establish client connection with the device")]
+    EstablishClient(#[source] crate::connection::Error),
+    #[error("communication with the device failed")]
+    Communicate(#[source] crate::connection::Error),
+    #[error(transparent)]
+    CreateOutput(crate::output::Error),
+    #[error(transparent)]
+    WriteOutput(crate::output::Error),
+    #[error(transparent)]
+    CommitOutput(crate::output::Error),
+}
+
+#[derive(serde::Serialize)]
+struct OutputSchema {
+    node: String,
+    property: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'static str>,
+}
+
+/// Decode the value and the target of the property, or the reason they are not available.
+fn decode(
+    property: &PropertyEntry,
+    values: &ModbusDeviceValues,
+) -> (Option<String>, Option<String>, Option<&'static str>) {
+    match property.kind.value_from_modbus(values) {
+        Some(Ok(value)) => (Some(value.value()), value.target(), None),
+        Some(Err(())) => (None, None, Some("invalid value")),
+        None => (None, None, Some("not readable")),
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+pub async fn run(args: Args) -> Result<(), Error> {
+    let known = homie::known_nodes();
+    let mut nodes = vec![];
+    for requested in &args.nodes {
+        let Some(node) = known.iter().find(|n| &n.node_id() == requested) else {
+            let ids = known.iter().map(|n| n.node_id().to_string()).collect::<Vec<_>>();
+            return Err(Error::NodeNotFound(requested.clone(), ids.join(", ")));
+        };
+        nodes.push(node);
+    }
+    if nodes.is_empty() {
+        nodes.extend(known.iter());
+    }
+
+    let mut registers = RegisterBitmask::new();
+    for property in nodes.iter().flat_map(|n| n.properties()) {
+        for register in property.kind.registers() {
+            registers.set(register.address());
+        }
+    }
+    let connection = Connection::new(args.connection).await.map_err(Error::EstablishClient)?;
+    let mut values = ModbusDeviceValues::new();
+    values.read_registers(&connection, &registers).await.map_err(Error::Communicate)?;
+
+    let mut output = args.output.to_output().map_err(Error::CreateOutput)?;
+    output.table_headers(vec!["Property", "Value", "Target"]).map_err(Error::WriteOutput)?;
+    for node in nodes {
+        let node_id = node.node_id();
+        for property in node.properties() {
+            // Actions have no state to show.
+            if property.kind.registers().is_empty() {
+                continue;
+            }
+            let (value, target, error) = decode(property, &values);
+            output
+                .result(
+                    || {
+                        vec![
+                            format!("{node_id}/{}", property.prop_id),
+                            value.clone().or(error.map(Into::into)).unwrap_or_default(),
+                            target.clone().unwrap_or_default(),
+                        ]
+                    },
+                    || OutputSchema {
+                        node: node_id.to_string(),
+                        property: property.prop_id.to_string(),
+                        value: value.clone(),
+                        target: target.clone(),
+                        error,
+                    },
+                )
+                .map_err(Error::WriteOutput)?;
+        }
+    }
+    output.commit().map_err(Error::CommitOutput)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registers::RegisterIndex;
+
+    fn property(node: &str, property: &str) -> &'static PropertyEntry {
+        let node = homie::known_nodes().into_iter().find(|n| n.node_id().as_str() == node).unwrap();
+        node.properties().iter().find(|p| p.prop_id.as_str() == property).unwrap()
+    }
+
+    fn values(registers: &[(&str, u16)]) -> ModbusDeviceValues {
+        let mut values = ModbusDeviceValues::new();
+        for &(name, value) in registers {
+            let register = RegisterIndex::from_name(name).unwrap();
+            values.set_value(register.address(), value);
+        }
+        values
+    }
+
+    #[test]
+    fn decodes_properties() {
+        let values = values(&[
+            ("FILTER_PERIOD", 12),
+            ("FILTER_REMAINING_TIME_L", 3600),
+            ("FILTER_REMAINING_TIME_H", 0),
+            ("DAY_OF_THE_WEEK", 2),
+        ]);
+        let (value, _, error) = decode(property("filter", "replacement-period"), &values);
+        assert_eq!((value.as_deref(), error), (Some("P12M"), None));
+        let (value, _, error) = decode(property("filter", "remaining-time"), &values);
+        assert_eq!((value.as_deref(), error), (Some("PT1H"), None));
+        let (value, _, error) = decode(property("clock", "weekday"), &values);
+        assert_eq!((value.as_deref(), error), (Some("wednesday"), None));
+    }
+
+    #[test]
+    fn undecodable_properties() {
+        let values = values(&[("DAY_OF_THE_WEEK", 99)]);
+        let weekday = decode(property("clock", "weekday"), &values);
+        assert_eq!(weekday, (None, None, Some("invalid value")));
+        let period = decode(property("filter", "replacement-period"), &values);
+        assert_eq!(period, (None, None, Some("not readable")));
+    }
+}
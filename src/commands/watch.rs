@@ -0,0 +1,121 @@
+//! Poll registers and output the ones that changed since the previous poll.
+//!
+//! The first poll outputs every selected register. A poll that fails is reported and skipped, so
+//! that a device that drops off the bus for a moment does not end the stream; values read after
+//! the device comes back are still compared against the last successful poll.
+
+use crate::connection::{self, Connection};
+use crate::modbus_device_cache::{ModbusDeviceValues, SetBitsIterator};
+use crate::output;
+use crate::registers::{DataType, RegisterIndex, Value};
+use std::collections::HashMap;
+
+/// Poll registers at an interval and output their values whenever they change.
+#[derive(clap::Parser)]
+pub struct Args {
+    /// Registers to watch.
+    ///
+    /// Each is a register address, a name, or a range of addresses such as `12100..12110` (end
+    /// exclusive), same as for `read`.
+    #[arg(required = true)]
+    registers: Vec<String>,
+    /// The time to wait between the polls.
+    #[arg(long, short = 'n', default_value = "5s")]
+    interval: humantime::Duration,
+    #[clap(flatten)]
+    requests: super::read::RequestArgs,
+    #[clap(flatten)]
+    connection: connection::Args,
+    #[clap(flatten)]
+    output: output::SampleArgs,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("could not select the registers to watch")]
+    Select(#[source] super::read::Error),
+    #[error("could establish client connection with the device")]
+    EstablishClient(#[source] crate::connection::Error),
+    #[error(transparent)]
+    CreateOutput(crate::output::Error),
+    #[error(transparent)]
+    WriteOutput(crate::output::Error),
+}
+
+#[derive(serde::Serialize)]
+struct OutputSchema {
+    timestamp: String,
+    address: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old: Option<Value>,
+    new: Value,
+}
+
+#[tokio::main(flavor = "current_thread")]
+pub async fn run(args: Args) -> Result<(), Error> {
+    let mask = super::read::parse_mask(&args.registers).map_err(Error::Select)?;
+    let mut output = args.output.to_output().map_err(Error::CreateOutput)?;
+    let heads = vec!["Time", "Address", "Name", "Old", "New"];
+    output.table_headers(heads).map_err(Error::WriteOutput)?;
+    let connection = Connection::new(args.connection).await.map_err(Error::EstablishClient)?;
+    let mut values = ModbusDeviceValues::new();
+    let options = args.requests.options();
+    let mut interval = tokio::time::interval(*args.interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        let previous = SetBitsIterator::new(&mask)
+            .filter_map(|address| Some((address, values.value_of_address(address)?)))
+            .collect::<HashMap<_, _>>();
+        let changed = match values.read_registers_with(&connection, &mask, &options).await {
+            Ok(changed) => changed,
+            Err(e) => {
+                tracing::warn!(
+                    error = (&e as &dyn std::error::Error),
+                    "could not read the registers, will retry at the next poll"
+                );
+                continue;
+            }
+        };
+        let now = jiff::Timestamp::now();
+        let timestamp = now.to_string();
+        for address in changed {
+            let index = RegisterIndex::from_address(address);
+            let data_type = index.map(|r| r.data_type()).unwrap_or(DataType::U16);
+            let old = previous.get(&address).map(|&word| data_type.from_word(word));
+            let new = data_type.from_word(values.value_of_address(address).expect("just read"));
+            output
+                .sample(
+                    || {
+                        vec![
+                            timestamp.clone(),
+                            address.to_string(),
+                            index.map(|r| r.name()).unwrap_or("???").to_string(),
+                            old.map(|v| v.to_string()).unwrap_or_default(),
+                            new.to_string(),
+                        ]
+                    },
+                    || OutputSchema {
+                        timestamp: timestamp.clone(),
+                        address,
+                        name: index.map(|r| r.name()),
+                        old,
+                        new,
+                    },
+                    || {
+                        Some(output::Sample {
+                            name: index?.name(),
+                            address,
+                            value: new.into(),
+                            device_id: Some(connection.device_id()),
+                            timestamp: Some(now),
+                        })
+                    },
+                )
+                .map_err(Error::WriteOutput)?;
+        }
+        output.flush().map_err(Error::WriteOutput)?;
+    }
+}
@@ -0,0 +1,170 @@
+//! Files listing the register values for `write --from`.
+//!
+//! JSON and TOML files hold a `registers` list of `{ register, value }` entries. This is the same
+//! shape as a snapshot (whose `name` keys are accepted in place of `register`), so the output of
+//! `dump` can be edited down and written directly. CSV files hold one `register,value` record per
+//! line, optionally preceded by a header. Registers are referred to by their name or address.
+
+use crate::snapshot::Number;
+use csv_core::ReadFieldResult;
+use std::path::{Path, PathBuf};
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum Format {
+    Csv,
+    Json,
+    Toml,
+}
+
+impl Format {
+    /// Pick the format based on the file extension, defaulting to CSV.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Format::Json,
+            Some("toml") => Format::Toml,
+            _ => Format::Csv,
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("could not read the register values at {1:?}")]
+    Read(#[source] std::io::Error, PathBuf),
+    #[error("could not parse the register values at {1:?} as JSON")]
+    ParseJson(#[source] serde_json::Error, PathBuf),
+    #[error("could not parse the register values at {1:?} as TOML")]
+    ParseToml(#[source] toml::de::Error, PathBuf),
+    #[error("line {1} of {0:?} is not a `register,value` record")]
+    ParseCsv(PathBuf, u64),
+}
+
+/// A register and the value to write into it, both as written in the file.
+pub struct Entry {
+    pub register: String,
+    pub value: String,
+}
+
+#[derive(serde::Deserialize)]
+struct Document {
+    registers: Vec<DocumentEntry>,
+}
+
+#[derive(serde::Deserialize)]
+struct DocumentEntry {
+    #[serde(alias = "name")]
+    register: Text,
+    value: Text,
+}
+
+/// Either a number or a string, both of which are accepted for registers and values.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum Text {
+    Number(Number),
+    String(String),
+}
+
+impl From<Text> for String {
+    fn from(text: Text) -> Self {
+        match text {
+            Text::Number(n) => n.to_string(),
+            Text::String(s) => s,
+        }
+    }
+}
+
+pub fn load(path: &Path, format: Format) -> Result<Vec<Entry>, Error> {
+    let data = std::fs::read_to_string(path).map_err(|e| Error::Read(e, path.into()))?;
+    let document: Document = match format {
+        Format::Csv => return parse_csv(path, &data),
+        Format::Json => {
+            serde_json::from_str(&data).map_err(|e| Error::ParseJson(e, path.into()))?
+        }
+        Format::Toml => toml::from_str(&data).map_err(|e| Error::ParseToml(e, path.into()))?,
+    };
+    Ok(document
+        .registers
+        .into_iter()
+        .map(|e| Entry { register: e.register.into(), value: e.value.into() })
+        .collect())
+}
+
+fn parse_csv(path: &Path, data: &str) -> Result<Vec<Entry>, Error> {
+    let mut reader = csv_core::ReaderBuilder::new().comment(Some(b'#')).build();
+    let mut input = data.as_bytes();
+    let mut field = vec![0; 64];
+    let mut field_len = 0;
+    let mut record = vec![];
+    let mut entries = vec![];
+    loop {
+        let (result, read, written) = reader.read_field(input, &mut field[field_len..]);
+        input = &input[read..];
+        field_len += written;
+        match result {
+            ReadFieldResult::InputEmpty => {}
+            ReadFieldResult::OutputFull => field.resize(field.len() * 2, 0),
+            ReadFieldResult::Field { record_end } => {
+                record.push(String::from_utf8_lossy(&field[..field_len]).trim().to_string());
+                field_len = 0;
+                if record_end {
+                    // Our own CSV output ends every record with a delimiter, so ignore empty
+                    // trailing fields.
+                    while record.len() > 2 && record.last().is_some_and(String::is_empty) {
+                        record.pop();
+                    }
+                    let Ok([register, value]) =
+                        <[String; 2]>::try_from(std::mem::take(&mut record))
+                    else {
+                        // The line count already includes the terminator, if the record had one.
+                        let consumed = &data.as_bytes()[..data.len() - input.len()];
+                        let line = reader.line() - u64::from(consumed.ends_with(b"\n"));
+                        return Err(Error::ParseCsv(path.into(), line));
+                    };
+                    let is_header = entries.is_empty()
+                        && ["register", "name"].iter().any(|h| register.eq_ignore_ascii_case(h))
+                        && value.eq_ignore_ascii_case("value");
+                    if !is_header {
+                        entries.push(Entry { register, value });
+                    }
+                }
+            }
+            ReadFieldResult::End => return Ok(entries),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(data: &str) -> Result<Vec<(String, String)>, Error> {
+        let entries = parse_csv(Path::new("test.csv"), data)?;
+        Ok(entries.into_iter().map(|e| (e.register, e.value)).collect())
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|&(r, v)| (r.into(), v.into())).collect()
+    }
+
+    #[test]
+    fn header_and_comments_are_skipped() {
+        let data = "register,value\n# a comment\n\nUSERMODE_MODE_HMI,1\n12101 , 20\n";
+        let expected = pairs(&[("USERMODE_MODE_HMI", "1"), ("12101", "20")]);
+        assert_eq!(parse(data).unwrap(), expected);
+    }
+
+    #[test]
+    fn quoted_fields_and_trailing_delimiters() {
+        let data = "\"Register\",\"Value\",\r\n\"SOME,NAME\",\"-1.5\",\r\n";
+        assert_eq!(parse(data).unwrap(), pairs(&[("SOME,NAME", "-1.5")]));
+    }
+
+    #[test]
+    fn malformed_records_name_their_line() {
+        let data = "A,1\n\nB\n";
+        assert!(matches!(parse(data), Err(Error::ParseCsv(_, 3))));
+        let data = "A,1\nB,2,3";
+        assert!(matches!(parse(data), Err(Error::ParseCsv(_, 2))));
+    }
+}
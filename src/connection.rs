@@ -23,7 +23,7 @@ pub enum Error {
     #[error("could not open {1:?} for reading and writing")]
     OpenDevice(#[source] std::io::Error, PathBuf),
     #[error("scheduling a request failed")]
-    ScheduleRequest(#[source] SendError<modbus::Request>),
+    ScheduleRequest(#[source] SendError<(modbus::Request, Duration)>),
     #[error("could not read data from the stream")]
     Receive(#[source] std::io::Error),
     #[error("could not shut down the connection")]
@@ -119,6 +119,18 @@ pub struct Args {
     server_busy_retry_delay: humantime::Duration,
 }
 
+impl Args {
+    /// Address the requests to a different modbus device ID.
+    pub fn set_device_id(&mut self, device_id: u8) {
+        self.how.device_id = device_id;
+    }
+
+    /// Pace the requests for a different baudrate.
+    pub fn set_baudrate(&mut self, baudrate: u32) {
+        self.baudrate = baudrate;
+    }
+}
+
 #[derive(clap::Parser, Clone)]
 #[group(required = true)]
 pub struct ConnectionGroup {
@@ -133,8 +145,40 @@ pub struct ConnectionGroup {
     device_id: u8,
 }
 
+/// [`Args`] for commands that only need to talk to the device in some of their modes.
+///
+/// The connection is only established when either `--tcp` or `--device` is specified.
+pub struct OptionalArgs(pub Option<Args>);
+
+impl clap::FromArgMatches for OptionalArgs {
+    fn from_arg_matches(matches: &clap::ArgMatches) -> Result<Self, clap::Error> {
+        let specified = ["tcp", "device"].into_iter().any(|id| matches.contains_id(id));
+        Ok(Self(specified.then(|| Args::from_arg_matches(matches)).transpose()?))
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &clap::ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
+
+impl clap::Args for OptionalArgs {
+    fn augment_args(cmd: clap::Command) -> clap::Command {
+        <Args as clap::Args>::augment_args(cmd)
+            .mut_group("ConnectionGroup", |g| g.required(false))
+            .mut_arg("device_id", |a| a.required(false))
+            .mut_arg("tcp", |a| a.requires("device_id"))
+            .mut_arg("device", |a| a.requires("device_id"))
+    }
+
+    fn augment_args_for_update(cmd: clap::Command) -> clap::Command {
+        Self::augment_args(cmd)
+    }
+}
+
 pub struct Connection {
-    pub request_queue: tokio::sync::mpsc::UnboundedSender<modbus::Request>,
+    /// The requests to send, along with the read timeout for each.
+    pub request_queue: tokio::sync::mpsc::UnboundedSender<(modbus::Request, Duration)>,
     pub worker: tokio::task::JoinHandle<Result<(), Error>>,
     pub response_tracker: Arc<ResponseTracker>,
     transaction_id_generator: std::sync::atomic::AtomicU16,
@@ -187,14 +231,33 @@ impl Connection {
         self.transaction_id_generator.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
     }
 
+    /// The modbus device ID the requests are addressed to.
+    pub fn device_id(&self) -> u8 {
+        self.args.how.device_id
+    }
+
+    /// The baudrate the requests are paced for.
+    pub fn baudrate(&self) -> u32 {
+        self.args.baudrate
+    }
+
     pub async fn send(
         &self,
         operation: modbus::Operation,
+    ) -> Result<Option<modbus::Response>, Error> {
+        self.send_with_timeout(operation, *self.args.read_timeout).await
+    }
+
+    /// [`Self::send`] but with a read timeout other than the `--read-timeout` of the connection.
+    pub async fn send_with_timeout(
+        &self,
+        operation: modbus::Operation,
+        read_timeout: Duration,
     ) -> Result<Option<modbus::Response>, Error> {
         let transaction_id = self.new_transaction_id();
         let request =
             modbus::Request { device_id: self.args.how.device_id, transaction_id, operation };
-        self.request_queue.send(request).map_err(Error::ScheduleRequest)?;
+        self.request_queue.send((request, read_timeout)).map_err(Error::ScheduleRequest)?;
         Ok(self.response_tracker.wait_for(transaction_id).await)
     }
 
@@ -223,7 +286,7 @@ impl Connection {
 struct TcpWorker {
     args: Args,
     responses: Arc<ResponseTracker>,
-    /// An in-order list of outstanding requests and their timeout instants.
+    /// An in-order list of outstanding requests, their timeout instants and when they were sent.
     ///
     /// This list is expected to be sorted by the order in which the requests were sent out, but
     /// not necessarily the transaction ID or the timeout.
@@ -252,7 +315,7 @@ struct TcpWorker {
     /// It may seem like a fancy data structure like `BTreeMap` or `tokio_util::time::DelayQueue`
     /// would be better here, but we don't expect to have more than 2-3 concurrent in-flight
     /// requests at a time. So linear scans are plenty good.
-    inflight: VecDeque<(u16, Instant)>,
+    inflight: VecDeque<(u16, Instant, Instant)>,
     reconnect_countdown: usize,
 }
 
@@ -261,23 +324,23 @@ type TcpIo = Framed<TcpStream, ModbusTCPCodec>;
 impl TcpWorker {
     fn spawn(
         self,
-        jobs: UnboundedReceiver<modbus::Request>,
+        jobs: UnboundedReceiver<(modbus::Request, Duration)>,
     ) -> tokio::task::JoinHandle<Result<(), Error>> {
         tokio::task::spawn(self.main_loop(jobs))
     }
 
     async fn main_loop(
         mut self,
-        mut jobs: UnboundedReceiver<modbus::Request>,
+        mut jobs: UnboundedReceiver<(modbus::Request, Duration)>,
     ) -> Result<(), Error> {
-        let mut pending_send: Option<modbus::Request> = None;
+        let mut pending_send: Option<(modbus::Request, Duration)> = None;
         'reconnect: loop {
             // If we are reconnecting and had any in-flight requests, it is only proper to report
             // them as timed out.
-            for (transaction_id, _) in self.inflight.drain(..) {
+            for (transaction_id, ..) in self.inflight.drain(..) {
                 self.responses.mark_timeout(transaction_id);
             }
-            if let Some(req) = pending_send.take() {
+            if let Some((req, _)) = pending_send.take() {
                 self.responses.mark_timeout(req.transaction_id);
             }
             // FIXME: shouldn't await here, these should be part of select!
@@ -312,14 +375,15 @@ impl TcpWorker {
                             );
                             continue 'reconnect;
                         }
-                        let req: Request = pending_send.take().unwrap();
+                        let (req, read_timeout): (Request, _) = pending_send.take().unwrap();
+                        let sent = Instant::now();
                         let resp_len = req.expected_response_length().into();
                         let baudrate = self.args.baudrate;
                         let response_duration = Duration::from_secs(resp_len) / (baudrate / 10);
-                        let response_ready_instant = Instant::now() + response_duration;
-                        let response_deadline = response_ready_instant + *self.args.read_timeout;
+                        let response_ready_instant = sent + response_duration;
+                        let response_deadline = response_ready_instant + read_timeout;
                         self.inflight
-                            .push_back((req.transaction_id, response_deadline));
+                            .push_back((req.transaction_id, response_deadline, sent));
                         recv_time.as_mut().reset(self.inflight[0].1);
                         send_time.as_mut().reset(response_ready_instant + *self.args.tcp_send_delay);
                     }
@@ -352,11 +416,11 @@ impl TcpWorker {
                                     return Ok(());
                                 }
                             },
-                            Some(req) => {
+                            Some((req, read_timeout)) => {
                                 // While we're sending, use `send_time` to track send timeout.
                                 send_time.as_mut().reset(Instant::now() + *self.args.send_timeout);
                                 io_sink.feed(req.clone()).await.map_err(Error::Send)?;
-                                assert!(pending_send.replace(req).is_none());
+                                assert!(pending_send.replace((req, read_timeout)).is_none());
                             }
                         }
                     },
@@ -383,10 +447,10 @@ impl TcpWorker {
         Ok(Framed::new(socket, ModbusTCPCodec {}))
     }
 
-    fn handle_response(&mut self, response: modbus::Response) {
+    fn handle_response(&mut self, mut response: modbus::Response) {
         trace!(message = "decoded a response", transaction = response.transaction_id);
         let inflight_index =
-            self.inflight.iter().position(|(id, _)| *id == response.transaction_id);
+            self.inflight.iter().position(|(id, ..)| *id == response.transaction_id);
         let Some(inflight_index) = inflight_index else {
             debug!(
                 message = "a response we were not expecting",
@@ -394,6 +458,7 @@ impl TcpWorker {
             );
             return;
         };
+        response.round_trip = Some(self.inflight[inflight_index].2.elapsed());
         if response.is_server_busy() {
             // IAM can respond with the busy code on its own, and it most
             // likely means that another request is being currently processed.
@@ -401,7 +466,7 @@ impl TcpWorker {
         } else {
             // Any requests sent out prior to the response we just received
             // were dropped, so lets time them out immediately.
-            for (tr_id, _) in self.inflight.drain(..inflight_index) {
+            for (tr_id, ..) in self.inflight.drain(..inflight_index) {
                 self.responses.mark_timeout(tr_id);
             }
             self.inflight.pop_front();
@@ -411,7 +476,7 @@ impl TcpWorker {
     }
 
     fn handle_timeout(&mut self, request_timeout: pin::Pin<&mut tokio::time::Sleep>) -> bool {
-        let (transaction_id, _) = self.inflight.pop_front().expect("unreachable");
+        let (transaction_id, ..) = self.inflight.pop_front().expect("unreachable");
         debug!(
             message = "an inflight request timed out",
             transaction_id,
@@ -423,7 +488,7 @@ impl TcpWorker {
         } else {
             return false;
         };
-        if let Some((_, timeout)) = self.inflight.front() {
+        if let Some((_, timeout, _)) = self.inflight.front() {
             request_timeout.reset(*timeout);
         }
         true
@@ -1,4 +1,4 @@
-mod alarm_node;
+pub(crate) mod alarm_node;
 mod clock_node;
 mod compensation_node;
 mod cooler_node;
@@ -8,11 +8,11 @@ mod filter_node;
 mod free_cooling_node;
 mod heat_exchanger_node;
 mod heater_node;
-mod input_node;
-mod mode_node;
-mod node;
+pub(crate) mod input_node;
+pub(crate) mod mode_node;
+pub(crate) mod node;
 mod temperature_controller_node;
-mod value;
+pub(crate) mod value;
 
 use crate::connection::{self, Connection};
 use crate::homie::node::Node;
@@ -141,6 +141,30 @@ pub struct Args {
         default_value = "alarm,clock,compensation,cooler,demand-control,fan-speed,filter,free-cooling,heater,heat-exchanger,mode,temperature-controller,inputs"
     )]
     nodes: Vec<HomieID>,
+
+    /// Unlock changes to the settings with this administrator password before every change
+    /// requested over MQTT.
+    #[arg(long, value_name = "PIN", conflicts_with = "read_only")]
+    unlock_with: Option<crate::commands::lock::Pin>,
+}
+
+/// All the nodes this tool knows how to expose.
+pub(crate) fn known_nodes() -> [Box<dyn Node>; 13] {
+    [
+        Box::new(alarm_node::AlarmNode::new()) as Box<dyn Node>,
+        Box::new(clock_node::ClockNode::new()) as Box<dyn Node>,
+        Box::new(compensation_node::CompensationNode::new()) as _,
+        Box::new(cooler_node::CoolerNode::new()) as _,
+        Box::new(demand_control_node::DemandControlNode::new()) as _,
+        Box::new(fan_speed_node::FanSpeedSettingsNode::new()) as _,
+        Box::new(filter_node::FilterNode::new()) as _,
+        Box::new(free_cooling_node::FreeCoolingNode::new()) as _,
+        Box::new(heater_node::HeaterNode::new()) as _,
+        Box::new(heat_exchanger_node::HeatExchangerNode::new()) as _,
+        Box::new(mode_node::ModeNode::new()) as _,
+        Box::new(temperature_controller_node::TemperatureControllerNode::new()) as _,
+        Box::new(input_node::InputNode::new()) as _,
+    ]
 }
 
 type EventStream = dyn Send + Sync + Stream<Item = Result<EventResult, connection::Error>>;
@@ -158,6 +182,8 @@ pub(crate) struct SystemAirDevice {
     modbus_values: ModbusDeviceValues,
     event_stream: AllEventStreams,
     commands: mpsc::UnboundedReceiver<Command>,
+    /// Held while the settings are unlocked with `--unlock-with` for a set.
+    unlocked: Arc<tokio::sync::Mutex<()>>,
 }
 
 impl SystemAirDevice {
@@ -169,22 +195,7 @@ impl SystemAirDevice {
         modbus: Arc<Connection>,
         commands: mpsc::UnboundedReceiver<Command>,
     ) -> Result<Self, Error> {
-        let known_nodes = [
-            Box::new(alarm_node::AlarmNode::new()) as Box<dyn Node>,
-            Box::new(clock_node::ClockNode::new()) as Box<dyn Node>,
-            Box::new(compensation_node::CompensationNode::new()) as _,
-            Box::new(cooler_node::CoolerNode::new()) as _,
-            Box::new(demand_control_node::DemandControlNode::new()) as _,
-            Box::new(fan_speed_node::FanSpeedSettingsNode::new()) as _,
-            Box::new(filter_node::FilterNode::new()) as _,
-            Box::new(free_cooling_node::FreeCoolingNode::new()) as _,
-            Box::new(heater_node::HeaterNode::new()) as _,
-            Box::new(heat_exchanger_node::HeatExchangerNode::new()) as _,
-            Box::new(mode_node::ModeNode::new()) as _,
-            Box::new(temperature_controller_node::TemperatureControllerNode::new()) as _,
-            Box::new(input_node::InputNode::new()) as _,
-        ];
-        let nodes = known_nodes
+        let nodes = known_nodes()
             .into_iter()
             .map(|v| (v.node_id(), v))
             .filter(|(i, _)| args.nodes.contains(i))
@@ -218,6 +229,7 @@ impl SystemAirDevice {
             modbus,
             modbus_values: ModbusDeviceValues::new(),
             event_stream: AllEventStreams::new(),
+            unlocked: Arc::default(),
         })
     }
 
@@ -528,11 +540,41 @@ impl SystemAirDevice {
                     return Ok(());
                 };
                 let task = property.kind.homie_set_to_modbus(
-                    node_id,
+                    node_id.clone(),
                     prop_idx,
                     Arc::clone(&self.modbus),
                     value,
                 );
+                let task = match self.args.unlock_with {
+                    None => task,
+                    Some(pin) => {
+                        let modbus = Arc::clone(&self.modbus);
+                        let unlocked = Arc::clone(&self.unlocked);
+                        Box::pin(async_stream::stream! {
+                            // Keep other sets from relocking the settings while this one is
+                            // still underway.
+                            let _unlocked = unlocked.lock().await;
+                            if let Err(e) = crate::commands::lock::unlock(&modbus, pin).await {
+                                tracing::warn!(
+                                    message = "could not unlock the settings",
+                                    error = (&e as &dyn std::error::Error)
+                                );
+                                let why = "could not unlock the settings";
+                                yield Ok(EventResult::HomieNotSet { node_id, prop_idx, why });
+                                return;
+                            }
+                            for await event in task {
+                                yield event;
+                            }
+                            if let Err(e) = crate::commands::lock::relock(&modbus).await {
+                                tracing::warn!(
+                                    message = "could not lock the settings again",
+                                    error = (&e as &dyn std::error::Error)
+                                );
+                            }
+                        }) as Pin<Box<EventStream>>
+                    }
+                };
                 self.event_stream.push(task);
             }
         }
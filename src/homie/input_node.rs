@@ -42,9 +42,9 @@ impl Node for InputNode {
 
 string_enum! {
     #[impl(TryFromValue, PropertyDescription, PropertyValue, RegisterPropertyValue)]
-    #[derive(Clone, Copy)]
+    #[derive(Clone, Copy, PartialEq)]
     #[repr(u16)]
-    enum DigitalInputConnection {
+    pub(crate) enum DigitalInputConnection {
         None = 0,
         AwayMode = 1,
         VacuumCleanerMode = 3,
@@ -66,12 +66,49 @@ string_enum! {
     }
 }
 
+impl DigitalInputConnection {
+    /// The register indicating whether the function connected to the input is active.
+    pub(crate) fn sensor_register(self) -> Option<&'static str> {
+        Some(match self {
+            Self::None | Self::ExtraControllerAlarm => return None,
+            Self::AwayMode => "SENSOR_DI_AWAY",
+            Self::VacuumCleanerMode => "SENSOR_DI_VACUUMCLEANER",
+            Self::CookerHoodMode => "SENSOR_DI_COOKERHOOD",
+            Self::CrowdedMode => "SENSOR_DI_CROWDED",
+            Self::ExtraControllerEmergencyThermostat => "SENSOR_DI_EXTRA_CONTROLLER_EMT",
+            Self::ExternalStop => "SENSOR_DI_EXTERNAL_STOP",
+            Self::FireplaceMode => "SENSOR_DI_FIREPLACE",
+            Self::HolidayMode => "SENSOR_DI_HOLIDAY",
+            Self::RefreshMode => "SENSOR_DI_REHRESH",
+            Self::RotorGuardSensor => "SENSOR_RGS",
+            Self::ChangeOverFeedback => "SENSOR_DI_CHANGE_OVER_FEEDBACK",
+            Self::FireAlarm => "SENSOR_DI_FIRE_ALARM",
+            Self::ConfigurableDigitalInput1Mode => "SENSOR_DI_CDI_1",
+            Self::ConfigurableDigitalInput2Mode => "SENSOR_DI_CDI_2",
+            Self::ConfigurableDigitalInput3Mode => "SENSOR_DI_CDI_3",
+            Self::PressureGuard => "SENSOR_DI_PRESSURE_GUARD",
+        })
+    }
+}
+
 string_enum! {
     #[impl(TryFromValue, PropertyDescription, PropertyValue, RegisterPropertyValue)]
-    #[derive(Clone, Copy)]
+    #[derive(Clone, Copy, PartialEq)]
     #[repr(u16)]
-    enum DigitalInputPolarity {
+    pub(crate) enum DigitalInputPolarity {
         NormallyOpen = 0,
         NormallyClosed = 1,
     }
 }
+
+string_enum! {
+    // Only some of the functions are known, see the description of `UI_1_FUNCTION`.
+    #[impl(TryFromValue)]
+    #[derive(Clone, Copy, PartialEq)]
+    #[repr(u16)]
+    pub(crate) enum UniversalInputFunction {
+        Co2Sensor = 2,
+        SupplyFanControl = 3,
+        ExtractFanControl = 4,
+    }
+}
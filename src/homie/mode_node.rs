@@ -88,8 +88,8 @@ impl Node for ModeNode {
 string_enum! {
     #[impl(TryFromValue, PropertyValue, PropertyDescription)]
     #[repr(u16)]
-    #[derive(Copy, Clone)]
-    enum CurrentMode {
+    #[derive(Copy, Clone, PartialEq)]
+    pub(crate) enum CurrentMode {
         Auto = 0,
         Manual = 1,
         Crowded = 2,
@@ -110,19 +110,12 @@ impl CurrentMode {
     fn new(value: Value) -> Result<Self, ()> {
         value.try_into()
     }
-}
 
-// This is using the `AggregatePropertyValue` as the writes go to a different
-// register and use different underlying modbus values.
-impl AggregatePropertyValue for CurrentMode {
-    const SETTABLE: bool = true;
-    fn set(
-        &self,
-        node_id: HomieID,
-        prop_idx: usize,
-        modbus: std::sync::Arc<crate::connection::Connection>,
-    ) -> std::pin::Pin<Box<super::EventStream>> {
-        let value = match self {
+    /// The value to write into `USERMODE_HMI_CHANGE_REQUEST` to switch into this mode.
+    ///
+    /// Modes that are only activated by the inputs cannot be requested.
+    pub(crate) fn request(self) -> Option<RequestMode> {
+        match self {
             CurrentMode::Auto => Some(RequestMode::Auto),
             CurrentMode::Manual => Some(RequestMode::Manual),
             CurrentMode::Crowded => Some(RequestMode::Crowded),
@@ -136,7 +129,21 @@ impl AggregatePropertyValue for CurrentMode {
             CurrentMode::ConfigurableDigitalInput2 => None,
             CurrentMode::ConfigurableDigitalInput3 => None,
             CurrentMode::PressureGuard => None,
-        };
+        }
+    }
+}
+
+// This is using the `AggregatePropertyValue` as the writes go to a different
+// register and use different underlying modbus values.
+impl AggregatePropertyValue for CurrentMode {
+    const SETTABLE: bool = true;
+    fn set(
+        &self,
+        node_id: HomieID,
+        prop_idx: usize,
+        modbus: std::sync::Arc<crate::connection::Connection>,
+    ) -> std::pin::Pin<Box<super::EventStream>> {
+        let value = self.request();
         Box::pin(async_stream::stream! {
             if let Some(value) = value {
                 let register = RegisterIndex::from_name("USERMODE_HMI_CHANGE_REQUEST").unwrap();
@@ -173,7 +180,7 @@ impl AggregatePropertyValue for CurrentMode {
 
 #[repr(u16)]
 #[derive(Copy, Clone)]
-enum RequestMode {
+pub(crate) enum RequestMode {
     Auto = 1,
     Manual = 2,
     Crowded = 3,
@@ -182,3 +189,20 @@ enum RequestMode {
     Away = 6,
     Holiday = 7,
 }
+
+impl RequestMode {
+    /// The register holding the duration of this mode, along with the unit it is stored in.
+    ///
+    /// Auto and manual modes stay on until changed and have no duration.
+    pub(crate) fn duration_register(self) -> Option<(RegisterIndex, jiff::Unit)> {
+        let (name, unit) = match self {
+            RequestMode::Auto | RequestMode::Manual => return None,
+            RequestMode::Crowded => ("USERMODE_CROWDED_TIME", jiff::Unit::Hour),
+            RequestMode::Refresh => ("USERMODE_REFRESH_TIME", jiff::Unit::Minute),
+            RequestMode::Fireplace => ("USERMODE_FIREPLACE_TIME", jiff::Unit::Minute),
+            RequestMode::Away => ("USERMODE_AWAY_TIME", jiff::Unit::Hour),
+            RequestMode::Holiday => ("USERMODE_HOLIDAY_TIME", jiff::Unit::Day),
+        };
+        Some((RegisterIndex::from_name(name).expect("mode duration registers are known"), unit))
+    }
+}
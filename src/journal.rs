@@ -0,0 +1,209 @@
+//! A local record of the register values changed by this tool.
+//!
+//! Every command that changes settings appends an operation listing the previous and new value of
+//! each register it changed, so that `write --undo` can put the previous values back. The journal
+//! is a JSON lines file kept at `$SYSTEMAIR_SAVE_TOOLS_JOURNAL`, or in the user's state directory
+//! (`~/.local/state/systemair-save-tools/journal.jsonl` by default).
+//!
+//! A few commands change registers without being journaled, as putting the previous value back
+//! would not undo them: `lock` only writes the unlock password (which should not end up in a file
+//! either), `alarms clear` clears alarms, which cannot be raised again, `override` restores the
+//! automatic state on its own, `mode` requests are one-off commands (although the mode duration
+//! it sets is journaled), and `doctor --fix` setting the clock would only put the clock off again
+//! if undone. Saving the user safe config does not change any settings.
+//!
+//! Undoing is recorded as an operation too, and refers to how many of the preceding operations it
+//! undid. Those are then skipped when looking for operations to undo next.
+
+use crate::registers::{ParseValueError, RegisterIndex, Value};
+use crate::snapshot::Number;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("could not determine where to keep the journal, set $SYSTEMAIR_SAVE_TOOLS_JOURNAL")]
+    NoLocation,
+    #[error("could not read the journal at {1:?}")]
+    Read(#[source] std::io::Error, PathBuf),
+    #[error("could not write the journal at {1:?}")]
+    Write(#[source] std::io::Error, PathBuf),
+    #[error("could not parse line {1} of the journal at {2:?}")]
+    Parse(#[source] serde_json::Error, usize, PathBuf),
+    #[error("could not serialize the journal entry")]
+    Serialize(#[source] serde_json::Error),
+    #[error("there are only {0} operations to undo in the journal")]
+    NotEnoughOperations(usize),
+    #[error("register `{0}` in the journal does not match any known register")]
+    RegisterNotFound(String),
+    #[error("could not parse value {1} for register {0} in the journal")]
+    ParseValue(&'static str, Number, #[source] ParseValueError),
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Operation {
+    pub timestamp: String,
+    pub command: String,
+    /// The number of preceding operations undone by this one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub undoes: Option<usize>,
+    pub changes: Vec<Change>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Change {
+    pub name: String,
+    pub address: u16,
+    pub before: Number,
+    pub after: Number,
+}
+
+impl Change {
+    /// Find the register this change refers to.
+    pub fn register(&self) -> Result<RegisterIndex, Error> {
+        RegisterIndex::from_name(&self.name)
+            .filter(|r| r.address() == self.address)
+            .ok_or_else(|| Error::RegisterNotFound(self.name.clone()))
+    }
+
+    /// The value of the register before the change.
+    pub fn before(&self, register: RegisterIndex) -> Result<Value, Error> {
+        register
+            .data_type()
+            .parse_string(&self.before.to_string())
+            .map_err(|e| Error::ParseValue(register.name(), self.before, e))
+    }
+}
+
+fn path() -> Result<PathBuf, Error> {
+    if let Some(path) = std::env::var_os("SYSTEMAIR_SAVE_TOOLS_JOURNAL") {
+        return Ok(path.into());
+    }
+    let state = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+        .ok_or(Error::NoLocation)?;
+    Ok(state.join("systemair-save-tools").join("journal.jsonl"))
+}
+
+fn append(operation: &Operation) -> Result<(), Error> {
+    let path = path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| Error::Write(e, path.clone()))?;
+    }
+    let mut line = serde_json::to_string(operation).map_err(Error::Serialize)?;
+    line.push('\n');
+    std::fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(|e| Error::Write(e, path))
+}
+
+fn changes(changes: impl IntoIterator<Item = (RegisterIndex, Value, Value)>) -> Vec<Change> {
+    changes
+        .into_iter()
+        .filter(|(_, before, after)| before != after)
+        .map(|(register, before, after)| Change {
+            name: register.name().to_string(),
+            address: register.address(),
+            before: before.into(),
+            after: after.into(),
+        })
+        .collect()
+}
+
+/// Record the `(register, before, after)` changes made by a command.
+///
+/// Registers whose value did not change are left out, and nothing is recorded if none did.
+pub fn record(
+    command: &str,
+    changed: impl IntoIterator<Item = (RegisterIndex, Value, Value)>,
+) -> Result<(), Error> {
+    let changes = changes(changed);
+    if changes.is_empty() {
+        return Ok(());
+    }
+    let timestamp = jiff::Timestamp::now().to_string();
+    append(&Operation { timestamp, command: command.to_string(), undoes: None, changes })
+}
+
+/// Record the changes made by undoing the specified number of operations.
+pub fn record_undo(
+    count: usize,
+    changed: impl IntoIterator<Item = (RegisterIndex, Value, Value)>,
+) -> Result<(), Error> {
+    let timestamp = jiff::Timestamp::now().to_string();
+    let command = "undo".to_string();
+    append(&Operation { timestamp, command, undoes: Some(count), changes: changes(changed) })
+}
+
+/// The last `count` operations that have not been undone yet, most recent first.
+pub fn last_operations(count: usize) -> Result<Vec<Operation>, Error> {
+    let path = path()?;
+    let data = match std::fs::read_to_string(&path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(Error::Read(e, path)),
+    };
+    let operations = pending_operations(&data, &path)?;
+    if operations.len() < count {
+        return Err(Error::NotEnoughOperations(operations.len()));
+    }
+    Ok(operations.into_iter().rev().take(count).collect())
+}
+
+/// The operations in the journal `data` that have not been undone, oldest first.
+fn pending_operations(data: &str, path: &Path) -> Result<Vec<Operation>, Error> {
+    let mut operations = vec![];
+    for (index, line) in data.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let operation: Operation =
+            serde_json::from_str(line).map_err(|e| Error::Parse(e, index + 1, path.into()))?;
+        match operation.undoes {
+            Some(undone) => operations.truncate(operations.len().saturating_sub(undone)),
+            None => operations.push(operation),
+        }
+    }
+    Ok(operations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending(lines: &[&str]) -> Vec<String> {
+        let data = lines.join("\n");
+        let operations = pending_operations(&data, Path::new("journal.jsonl")).unwrap();
+        operations.into_iter().map(|operation| operation.command).collect()
+    }
+
+    fn operation(command: &str) -> String {
+        format!(r#"{{"timestamp":"","command":"{command}","changes":[]}}"#)
+    }
+
+    fn undo(count: usize) -> String {
+        format!(r#"{{"timestamp":"","command":"undo","undoes":{count},"changes":[]}}"#)
+    }
+
+    #[test]
+    fn undone_operations_are_skipped() {
+        let (a, b, c) = (operation("a"), operation("b"), operation("c"));
+        assert_eq!(pending(&[&a, &b, &c]), ["a", "b", "c"]);
+        assert_eq!(pending(&[&a, &b, &c, &undo(2)]), ["a"]);
+        assert_eq!(pending(&[&a, &b, &undo(1), &c, "", &undo(1)]), ["a"]);
+        // Undoing successively works through older operations.
+        assert_eq!(pending(&[&a, &b, &c, &undo(1), &undo(1)]), ["a"]);
+        assert_eq!(pending(&[&a, &undo(5), &b]), ["b"]);
+    }
+
+    #[test]
+    fn malformed_lines_name_their_line() {
+        let data = [operation("a"), String::new(), "{".into()].join("\n");
+        let result = pending_operations(&data, Path::new("journal.jsonl"));
+        assert!(matches!(result, Err(Error::Parse(_, 3, _))));
+    }
+}
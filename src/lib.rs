@@ -1,7 +1,9 @@
 pub mod commands;
 pub mod connection;
 pub mod homie;
+pub mod journal;
 pub mod modbus;
 pub mod modbus_device_cache;
 pub mod output;
 pub mod registers;
+pub mod snapshot;
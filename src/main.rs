@@ -9,6 +9,22 @@ enum Commands {
     Read(commands::read::Args),
     Write(commands::write::Args),
     Mqtt(commands::mqtt::Args),
+    Scan(commands::scan::Args),
+    Dump(commands::dump::Args),
+    Restore(commands::restore::Args),
+    Diff(commands::diff::Args),
+    Watch(commands::watch::Args),
+    Log(commands::log::Args),
+    Schedule(commands::schedule::Args),
+    Alarms(commands::alarms::Args),
+    Mode(commands::mode::Args),
+    Doctor(commands::doctor::Args),
+    Lock(commands::lock::Args),
+    Config(commands::config::Args),
+    CommSettings(commands::comm_settings::Args),
+    Inputs(commands::inputs::Args),
+    Override(commands::manual_override::Args),
+    Status(commands::status::Args),
 }
 
 fn end<E: std::error::Error>(r: Result<(), E>) {
@@ -44,5 +60,21 @@ fn main() {
         Commands::Read(args) => end(commands::read::run(args)),
         Commands::Write(args) => end(commands::write::run(args)),
         Commands::Mqtt(args) => end(commands::mqtt::run(args)),
+        Commands::Scan(args) => end(commands::scan::run(args)),
+        Commands::Dump(args) => end(commands::dump::run(args)),
+        Commands::Restore(args) => end(commands::restore::run(args)),
+        Commands::Diff(args) => end(commands::diff::run(args)),
+        Commands::Watch(args) => end(commands::watch::run(args)),
+        Commands::Log(args) => end(commands::log::run(args)),
+        Commands::Schedule(args) => end(commands::schedule::run(args)),
+        Commands::Alarms(args) => end(commands::alarms::run(args)),
+        Commands::Mode(args) => end(commands::mode::run(args)),
+        Commands::Doctor(args) => end(commands::doctor::run(args)),
+        Commands::Lock(args) => end(commands::lock::run(args)),
+        Commands::Config(args) => end(commands::config::run(args)),
+        Commands::CommSettings(args) => end(commands::comm_settings::run(args)),
+        Commands::Inputs(args) => end(commands::inputs::run(args)),
+        Commands::Override(args) => end(commands::manual_override::run(args)),
+        Commands::Status(args) => end(commands::status::run(args)),
     }
 }
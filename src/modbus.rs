@@ -40,6 +40,8 @@ pub struct Response {
     pub device_id: u8,
     pub transaction_id: u16,
     pub kind: ResponseKind,
+    /// The time from writing out the request to receiving this response, if known.
+    pub round_trip: Option<std::time::Duration>,
 }
 
 impl Response {
@@ -150,6 +152,7 @@ impl Decoder for ModbusTCPCodec {
                     transaction_id,
                     device_id,
                     kind: ResponseKind::ErrorCode(code),
+                    round_trip: None,
                 }));
             } else {
                 // NOTE: The `code` variable in the case of success might store the length of the
@@ -177,6 +180,7 @@ impl Decoder for ModbusTCPCodec {
                         }
                         _ => continue,
                     },
+                    round_trip: None,
                 }));
                 src.advance(usize::from(required_length) + 6);
                 return result;
@@ -1,6 +1,14 @@
+use crate::connection::{self, Connection};
+use crate::modbus::{self, Operation, Response, ResponseKind};
 use crate::registers::{RegisterIndex, Value};
+use futures::{StreamExt as _, TryStreamExt as _};
+use std::collections::BTreeMap;
+use std::time::Duration;
 
-pub(crate) struct RegisterBitmask([u64; u16::MAX as usize / u64::BITS as usize]);
+/// The number of distinct register addresses.
+const ADDRESS_COUNT: usize = u16::MAX as usize + 1;
+
+pub(crate) struct RegisterBitmask([u64; ADDRESS_COUNT / u64::BITS as usize]);
 
 impl RegisterBitmask {
     pub(crate) fn new() -> Self {
@@ -120,7 +128,7 @@ impl<'a> Iterator for SetBitsIterator<'a> {
 }
 
 pub(crate) struct ModbusDeviceValues {
-    values: [u16; u16::MAX as usize],
+    values: [u16; ADDRESS_COUNT],
     have_value: RegisterBitmask,
 }
 
@@ -159,4 +167,315 @@ impl ModbusDeviceValues {
     pub(crate) fn has_all_values(&self, address_mask: &RegisterBitmask) -> bool {
         address_mask.is_subset_of(&self.have_value)
     }
+
+    /// Read out the registers set in `registers` from the device into this cache.
+    ///
+    /// Registers that could not be read out are left without a value. See [`read_outcomes`] for
+    /// how the reads are made.
+    ///
+    /// Returns the addresses whose values have changed, as determined by [`Self::set_value`].
+    pub(crate) async fn read_registers(
+        &mut self,
+        connection: &Connection,
+        registers: &RegisterBitmask,
+    ) -> Result<Vec<u16>, connection::Error> {
+        self.read_registers_with(connection, registers, &ReadOptions::DEFAULT).await
+    }
+
+    /// [`Self::read_registers`] but with other than the default [`ReadOptions`].
+    pub(crate) async fn read_registers_with(
+        &mut self,
+        connection: &Connection,
+        registers: &RegisterBitmask,
+        options: &ReadOptions,
+    ) -> Result<Vec<u16>, connection::Error> {
+        let mut changed = vec![];
+        let mut unanswered = 0;
+        for (address, outcome) in read_outcomes(connection, registers, options).await? {
+            match outcome {
+                ReadOutcome::Value { word, .. } => {
+                    if self.set_value(address, word) {
+                        changed.push(address);
+                    }
+                }
+                ReadOutcome::Exception { code, .. } => {
+                    tracing::warn!(address, code, "could not read register")
+                }
+                ReadOutcome::NoResponse => unanswered += 1,
+            }
+        }
+        if unanswered > 0 {
+            tracing::warn!(unanswered, "some registers did not respond to the reads");
+        }
+        Ok(changed)
+    }
+}
+
+/// How persistently to read registers out of the device.
+#[derive(Clone, Copy)]
+pub(crate) struct ReadOptions {
+    /// The number of read requests to keep in flight at the same time.
+    pub pipeline_depth: u16,
+    /// The timeout for a single attempt, instead of the `--read-timeout` of the connection.
+    pub register_timeout: Option<Duration>,
+    /// The number of times to retry a read that timed out or found the device busy.
+    pub max_retries: u32,
+}
+
+impl ReadOptions {
+    pub(crate) const DEFAULT: Self =
+        Self { pipeline_depth: 2, register_timeout: None, max_retries: 3 };
+}
+
+/// When and how quickly the response to a request arrived.
+#[derive(Clone, Copy)]
+pub(crate) struct Received {
+    pub transaction_id: u16,
+    pub timestamp: jiff::Timestamp,
+    /// From writing out the request to receiving the response, excluding the time queued.
+    pub latency: Duration,
+}
+
+/// The outcome of reading out a single register.
+pub(crate) enum ReadOutcome {
+    Value { received: Received, word: u16 },
+    Exception { received: Received, code: u8 },
+    NoResponse,
+}
+
+/// Read out the registers set in `registers`, in as few requests as possible.
+///
+/// Should the device reject a batch with an exception, the registers within it are read out one
+/// by one instead, so that a single unreadable register does not hide the others.
+pub(crate) async fn read_outcomes(
+    connection: &Connection,
+    registers: &RegisterBitmask,
+    options: &ReadOptions,
+) -> Result<BTreeMap<u16, ReadOutcome>, connection::Error> {
+    let ranges = registers.find_optimal_ranges(modbus::MAX_SAFE_READ_COUNT);
+    let outcomes = futures::stream::iter(ranges)
+        .map(|range| read_range(connection, range, registers, options))
+        .buffered(usize::from(options.pipeline_depth))
+        .try_collect::<Vec<_>>()
+        .await?;
+    Ok(outcomes.into_iter().flatten().collect())
+}
+
+/// Read out the registers set in `mask` within the `range`.
+async fn read_range(
+    connection: &Connection,
+    range: std::ops::RangeInclusive<u16>,
+    mask: &RegisterBitmask,
+    options: &ReadOptions,
+) -> Result<Vec<(u16, ReadOutcome)>, connection::Error> {
+    let address = *range.start();
+    let count = range.end() - range.start() + 1;
+    let wanted = range.filter(|a| mask.is_set(*a));
+    let response = send(connection, Operation::GetHoldings { address, count }, options).await?;
+    let Some((Response { kind, .. }, received)) = response else {
+        return Ok(wanted.map(|a| (a, ReadOutcome::NoResponse)).collect());
+    };
+    Ok(match kind {
+        ResponseKind::GetHoldings { values } => {
+            let (words, _) = values.as_chunks::<2>();
+            let word = |a: u16| words.get(usize::from(a - address)).copied();
+            wanted
+                .map(|a| match word(a) {
+                    Some(word) => {
+                        (a, ReadOutcome::Value { received, word: u16::from_be_bytes(word) })
+                    }
+                    None => (a, ReadOutcome::NoResponse),
+                })
+                .collect()
+        }
+        ResponseKind::ErrorCode(code) if count > 1 => {
+            tracing::debug!(address, count, code, "batch rejected, reading one by one");
+            let mut outcomes = vec![];
+            for address in wanted {
+                let single = address..=address;
+                outcomes.extend(Box::pin(read_range(connection, single, mask, options)).await?);
+            }
+            outcomes
+        }
+        ResponseKind::ErrorCode(code) => {
+            wanted.map(|a| (a, ReadOutcome::Exception { received, code })).collect()
+        }
+        ResponseKind::SetHoldings { .. } => {
+            tracing::warn!(address, count, "unexpected response to a read");
+            wanted.map(|a| (a, ReadOutcome::NoResponse)).collect()
+        }
+    })
+}
+
+/// Send the request, retrying timeouts and `Server Busy` exceptions up to `max_retries` times.
+///
+/// Returns `None` if the device did not respond in the end.
+async fn send(
+    connection: &Connection,
+    operation: Operation,
+    options: &ReadOptions,
+) -> Result<Option<(Response, Received)>, connection::Error> {
+    for attempt in 0..=options.max_retries {
+        let sent = std::time::Instant::now();
+        let response = match options.register_timeout {
+            None => connection.send(operation.clone()).await?,
+            Some(timeout) => connection.send_with_timeout(operation.clone(), timeout).await?,
+        };
+        match response {
+            Some(response) if response.is_server_busy() => connection.handle_server_busy().await,
+            Some(response) => {
+                let received = Received {
+                    transaction_id: response.transaction_id,
+                    timestamp: jiff::Timestamp::now(),
+                    latency: response.round_trip.unwrap_or_else(|| sent.elapsed()),
+                };
+                return Ok(Some((response, received)));
+            }
+            None => tracing::debug!(?operation, attempt, "read timed out"),
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+    #[test]
+    fn bitmask_covers_all_addresses() {
+        let mut mask = RegisterBitmask::new();
+        for address in [0, 63, 64, 65471, 65472, 65500, u16::MAX] {
+            mask.set(address);
+            assert!(mask.is_set(address));
+        }
+        let set = SetBitsIterator::new(&mask).collect::<Vec<_>>();
+        assert_eq!(set, [0, 63, 64, 65471, 65472, 65500, u16::MAX]);
+        mask.clear(u16::MAX);
+        assert!(!mask.is_set(u16::MAX));
+    }
+
+    #[test]
+    fn optimal_ranges_at_the_end_of_the_address_space() {
+        let mut mask = RegisterBitmask::new();
+        mask.set(65500);
+        mask.set(65501);
+        mask.set(u16::MAX);
+        assert_eq!(mask.find_optimal_ranges(modbus::MAX_SAFE_READ_COUNT), [65500..=u16::MAX]);
+        assert_eq!(mask.find_optimal_ranges(2), [65500..=65501, u16::MAX..=u16::MAX]);
+    }
+
+    #[test]
+    fn values_at_the_highest_address() {
+        let mut values = ModbusDeviceValues::new();
+        assert!(values.set_value(u16::MAX, 42));
+        assert_eq!(values.value_of_address(u16::MAX), Some(42));
+        assert!(!values.set_value(u16::MAX, 42));
+    }
+
+    /// The PDU the fake device answers a request with the given function code with, if any.
+    type Reply = Option<fn(u8) -> Vec<u8>>;
+
+    /// A Modbus TCP device answering the requests with the `replies` in turn, repeating the
+    /// last one. `None` leaves the request unanswered.
+    ///
+    /// Returns the address of the device and the number of requests it has received.
+    async fn fake_device(replies: Vec<Reply>) -> (String, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let received = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&received);
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = [0; 12];
+                while stream.read_exact(&mut request).await.is_ok() {
+                    let index = counter.fetch_add(1, Ordering::SeqCst);
+                    let Some(reply) = replies[index.min(replies.len() - 1)] else { continue };
+                    let pdu = reply(request[7]);
+                    let length = u16::try_from(pdu.len() + 1).unwrap().to_be_bytes();
+                    let header = [request[0], request[1], 0, 0, length[0], length[1], request[6]];
+                    let _ = stream.write_all(&[&header[..], &pdu].concat()).await;
+                }
+            }
+        });
+        (address, received)
+    }
+
+    fn busy(function: u8) -> Vec<u8> {
+        vec![function | 0x80, 6]
+    }
+
+    fn value(function: u8) -> Vec<u8> {
+        vec![function, 2, 0, 42]
+    }
+
+    async fn connect(address: &str) -> Connection {
+        #[derive(clap::Parser)]
+        struct Cli {
+            #[clap(flatten)]
+            connection: connection::Args,
+        }
+        let cli = <Cli as clap::Parser>::parse_from([
+            "test",
+            "--tcp",
+            address,
+            "--device-id=1",
+            "--read-timeout=20ms",
+            "--tcp-send-delay=0ms",
+            "--server-busy-retry-delay=0ms",
+            "--reconnect-after-timeouts=100",
+            "--baudrate=1000000",
+        ]);
+        Connection::new(cli.connection).await.unwrap()
+    }
+
+    async fn send_to(address: &str, max_retries: u32) -> Option<(Response, Received)> {
+        let connection = connect(address).await;
+        let operation = Operation::GetHoldings { address: 12, count: 1 };
+        let options = ReadOptions { max_retries, ..ReadOptions::DEFAULT };
+        send(&connection, operation, &options).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn retries_timeouts() {
+        let (address, received) = fake_device(vec![None]).await;
+        assert!(send_to(&address, 2).await.is_none());
+        assert_eq!(received.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn retries_busy_devices() {
+        let (address, received) = fake_device(vec![Some(busy), Some(busy), Some(value)]).await;
+        let (response, _) = send_to(&address, 2).await.expect("third attempt succeeds");
+        assert!(matches!(response.kind, ResponseKind::GetHoldings { .. }));
+        assert_eq!(received.load(Ordering::SeqCst), 3);
+        let (address, received) = fake_device(vec![Some(busy), Some(busy), Some(value)]).await;
+        assert!(send_to(&address, 1).await.is_none());
+        assert_eq!(received.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn measures_the_answered_attempt() {
+        let (address, _) = fake_device(vec![Some(busy), Some(value)]).await;
+        let started = std::time::Instant::now();
+        let (response, received) = send_to(&address, 1).await.expect("second attempt succeeds");
+        assert_eq!((received.transaction_id, response.transaction_id), (1, 1));
+        assert_eq!(Some(received.latency), response.round_trip);
+        assert!(received.latency <= started.elapsed());
+    }
+
+    #[tokio::test]
+    async fn unanswered_reads_leave_registers_without_values() {
+        let mut mask = RegisterBitmask::new();
+        mask.set(12);
+        let mut values = ModbusDeviceValues::new();
+        let (address, _) = fake_device(vec![None]).await;
+        let changed = values.read_registers(&connect(&address).await, &mask).await.unwrap();
+        assert!(changed.is_empty() && !values.contains(12));
+        let (address, _) = fake_device(vec![Some(busy), Some(value)]).await;
+        let changed = values.read_registers(&connect(&address).await, &mask).await.unwrap();
+        assert_eq!((changed, values.value_of_address(12)), (vec![12], Some(42)));
+    }
 }
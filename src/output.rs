@@ -1,5 +1,7 @@
+use std::collections::BTreeSet;
 use std::path::PathBuf;
 
+use crate::snapshot::Number;
 use csv_core::WriteResult;
 
 #[derive(clap::ValueEnum, Clone, Debug)]
@@ -9,7 +11,19 @@ pub enum Format {
     Csv,
 }
 
-#[derive(clap::Parser)]
+/// The formats of the commands outputting register values, which can also be output as metrics.
+#[derive(clap::ValueEnum, Clone, Debug)]
+pub enum SampleFormat {
+    Table,
+    Jsonl,
+    Csv,
+    /// The Prometheus text exposition format, e.g. for the node_exporter textfile collector.
+    Prometheus,
+    /// The InfluxDB line protocol, e.g. for `influx write`.
+    Influx,
+}
+
+#[derive(clap::Parser, Clone)]
 #[group(id = "output::Args")]
 pub struct Args {
     #[arg(long, short = 'o')]
@@ -18,6 +32,16 @@ pub struct Args {
     format: Format,
 }
 
+/// [`Args`] for the commands outputting register values with [`Output::sample`].
+#[derive(clap::Parser, Clone)]
+#[group(id = "output::SampleArgs")]
+pub struct SampleArgs {
+    #[arg(long, short = 'o')]
+    output: Option<PathBuf>,
+    #[arg(long, short='f', value_enum, default_value_t = SampleFormat::Table)]
+    format: SampleFormat,
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("could not open the specified output file at {1:?}")]
@@ -32,7 +56,37 @@ pub enum Error {
     SerializeCsv(#[source] std::io::Error),
 }
 
+/// A register value, as output by the `prometheus` and `influx` formats.
+pub struct Sample {
+    pub name: &'static str,
+    pub address: u16,
+    /// The value with the scale of the register applied.
+    pub value: Number,
+    pub device_id: Option<u8>,
+    pub timestamp: Option<jiff::Timestamp>,
+}
+
+/// The InfluxDB measurement the register values are written into.
+const INFLUX_MEASUREMENT: &str = "systemair";
+
 impl Args {
+    pub fn to_output(self) -> Result<Output, Error> {
+        SampleArgs::from(self).to_output()
+    }
+}
+
+impl From<Args> for SampleArgs {
+    fn from(args: Args) -> Self {
+        let format = match args.format {
+            Format::Table => SampleFormat::Table,
+            Format::Jsonl => SampleFormat::Jsonl,
+            Format::Csv => SampleFormat::Csv,
+        };
+        SampleArgs { output: args.output, format }
+    }
+}
+
+impl SampleArgs {
     pub fn to_output(self) -> Result<Output, Error> {
         let io = match &self.output {
             None => Box::new(std::io::stdout().lock()) as Box<_>,
@@ -46,28 +100,40 @@ impl Args {
             ) as Box<_>,
         };
         let formatter = match &self.format {
-            Format::Table => {
+            SampleFormat::Table => {
                 let mut comfy = comfy_table::Table::new();
                 comfy.set_content_arrangement(comfy_table::ContentArrangement::Dynamic);
                 Formatter::Table { comfy }
             }
-            Format::Jsonl => Formatter::Jsonl,
-            Format::Csv => Formatter::Csv { written_records: false },
+            SampleFormat::Jsonl => Formatter::Jsonl,
+            SampleFormat::Csv => Formatter::Csv { written_records: false },
+            SampleFormat::Prometheus => Formatter::Prometheus { seen: BTreeSet::new() },
+            SampleFormat::Influx => Formatter::Influx,
         };
-        Ok(Output { args: self, io, formatter })
+        Ok(Output { path: self.output, io, formatter })
     }
 }
 
 pub struct Output {
-    args: Args,
+    path: Option<PathBuf>,
     io: Box<dyn std::io::Write>,
     formatter: Formatter,
 }
 
 enum Formatter {
-    Csv { written_records: bool },
-    Table { comfy: comfy_table::Table },
+    Csv {
+        written_records: bool,
+    },
+    Table {
+        comfy: comfy_table::Table,
+    },
     Jsonl,
+    /// The metrics output since the last flush. Every metric is only output once, as Prometheus
+    /// does not accept duplicate samples.
+    Prometheus {
+        seen: BTreeSet<String>,
+    },
+    Influx,
 }
 
 impl Output {
@@ -83,7 +149,7 @@ impl Output {
             Formatter::Table { comfy } => {
                 comfy.set_header(hdrs);
             }
-            Formatter::Jsonl => {}
+            Formatter::Jsonl | Formatter::Prometheus { .. } | Formatter::Influx => {}
         }
         Ok(())
     }
@@ -92,31 +158,35 @@ impl Output {
         &mut self,
         values: &[V],
     ) -> Result<(), Error> {
-        let max_len = 2 + 2 * values.iter().map(|v| v.len()).max().unwrap_or(0);
-        let mut output = vec![0; max_len];
-        let mut writer = csv_core::Writer::new();
-        for value in values {
-            let inp = value.as_bytes();
-            let (WriteResult::InputEmpty, ib, ob) = writer.field(inp, &mut output) else {
-                panic!("something wrong with csv output");
-            };
-            assert_eq!(value.len(), ib);
-            self.io.write_all(&output[..ob]).map_err(|e| self.write_error(e))?;
-            let (WriteResult::InputEmpty, ob) = writer.delimiter(&mut output) else {
-                panic!("something wrong with csv output");
-            };
-            self.io.write_all(&output[..ob]).map_err(|e| self.write_error(e))?;
-        }
-        let (WriteResult::InputEmpty, ob) = writer.terminator(&mut output) else {
-            panic!("something wrong with csv output");
-        };
-        self.io.write_all(&output[..ob]).map_err(|e| self.write_error(e))
+        write_csv_record(&mut self.io, values).map_err(|e| self.write_error(e))
     }
 
     pub fn result<R: serde::Serialize>(
         &mut self,
         table_row: impl FnOnce() -> Vec<String>,
         serde_record: impl FnOnce() -> R,
+    ) -> Result<(), Error> {
+        self.record(table_row, serde_record, None::<fn() -> Option<Sample>>)
+    }
+
+    /// [`Self::result`] for records holding a register value, which can also be output as metrics.
+    ///
+    /// Records for which `sample` returns `None` (e.g. as the register could not be read) are
+    /// left out of the metrics.
+    pub fn sample<R: serde::Serialize>(
+        &mut self,
+        table_row: impl FnOnce() -> Vec<String>,
+        serde_record: impl FnOnce() -> R,
+        sample: impl FnOnce() -> Option<Sample>,
+    ) -> Result<(), Error> {
+        self.record(table_row, serde_record, Some(sample))
+    }
+
+    fn record<R: serde::Serialize>(
+        &mut self,
+        table_row: impl FnOnce() -> Vec<String>,
+        serde_record: impl FnOnce() -> R,
+        sample: Option<impl FnOnce() -> Option<Sample>>,
     ) -> Result<(), Error> {
         match &mut self.formatter {
             Formatter::Csv { written_records } => {
@@ -132,25 +202,177 @@ impl Output {
                     .map_err(Error::SerializeJson)?;
                 writeln!(self.io).map_err(|e| self.write_error(e))?
             }
+            Formatter::Prometheus { seen } => {
+                let sample = sample.expect("metric formats are only offered with samples");
+                let Some(sample) = sample() else { return Ok(()) };
+                let metric = sample.name.to_ascii_lowercase();
+                if !seen.insert(metric.clone()) {
+                    return Ok(());
+                }
+                let mut labels = format!("address=\"{}\"", sample.address);
+                if let Some(device_id) = sample.device_id {
+                    labels.push_str(&format!(",device_id=\"{device_id}\""));
+                }
+                writeln!(self.io, "# TYPE {metric} gauge\n{metric}{{{labels}}} {}", sample.value)
+                    .map_err(|e| self.write_error(e))?
+            }
+            Formatter::Influx => {
+                let sample = sample.expect("metric formats are only offered with samples");
+                let Some(sample) = sample() else { return Ok(()) };
+                let mut line = format!("{INFLUX_MEASUREMENT},address={}", sample.address);
+                if let Some(device_id) = sample.device_id {
+                    line.push_str(&format!(",device_id={device_id}"));
+                }
+                line.push_str(&format!(" {}={}", sample.name, sample.value));
+                if let Some(timestamp) = sample.timestamp {
+                    line.push_str(&format!(" {}", timestamp.as_nanosecond()));
+                }
+                writeln!(self.io, "{line}").map_err(|e| self.write_error(e))?
+            }
         }
         Ok(())
     }
 
     fn write_error(&self, e: std::io::Error) -> Error {
-        match &self.args.output {
+        match &self.path {
             None => Error::WriteStdout(e),
             Some(p) => Error::WriteFile(e, p.into()),
         }
     }
 
+    /// Write out the results accumulated so far.
+    ///
+    /// Meant for commands that keep producing results until interrupted. Tables are printed as
+    /// a separate table for each flush, and metrics may be output again after one.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        if let Formatter::Prometheus { seen } = &mut self.formatter {
+            seen.clear();
+        }
+        if let Formatter::Table { comfy } = &mut self.formatter {
+            if comfy.is_empty() {
+                return Ok(());
+            }
+            let mut next = comfy_table::Table::new();
+            next.set_content_arrangement(comfy_table::ContentArrangement::Dynamic);
+            if let Some(header) = comfy.header() {
+                next.set_header(header.clone());
+            }
+            let comfy = std::mem::replace(comfy, next);
+            writeln!(self.io, "{}", comfy).map_err(|e| self.write_error(e))?;
+        }
+        self.io.flush().map_err(|e| self.write_error(e))
+    }
+
     pub fn commit(mut self) -> Result<(), Error> {
         match &self.formatter {
             Formatter::Csv { written_records: _ } => {}
             Formatter::Table { comfy } => {
                 self.io.write_fmt(format_args!("{}", comfy)).map_err(|e| self.write_error(e))?;
             }
-            Formatter::Jsonl => {}
+            Formatter::Jsonl | Formatter::Prometheus { .. } | Formatter::Influx => {}
         }
         self.io.flush().map_err(|e| self.write_error(e))
     }
 }
+
+/// Write out a single CSV record terminated by a newline.
+///
+/// Every field is followed by a delimiter, including the last one.
+pub(crate) fn write_csv_record<V: std::ops::Deref<Target = str>>(
+    io: &mut dyn std::io::Write,
+    values: &[V],
+) -> Result<(), std::io::Error> {
+    let max_len = 2 + 2 * values.iter().map(|v| v.len()).max().unwrap_or(0);
+    let mut output = vec![0; max_len];
+    let mut writer = csv_core::Writer::new();
+    for value in values {
+        let inp = value.as_bytes();
+        let (WriteResult::InputEmpty, ib, ob) = writer.field(inp, &mut output) else {
+            panic!("something wrong with csv output");
+        };
+        assert_eq!(value.len(), ib);
+        io.write_all(&output[..ob])?;
+        let (WriteResult::InputEmpty, ob) = writer.delimiter(&mut output) else {
+            panic!("something wrong with csv output");
+        };
+        io.write_all(&output[..ob])?;
+    }
+    let (WriteResult::InputEmpty, ob) = writer.terminator(&mut output) else {
+        panic!("something wrong with csv output");
+    };
+    io.write_all(&output[..ob])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// An output buffer that stays accessible after being handed to the [`Output`].
+    #[derive(Clone, Default)]
+    struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+    impl std::io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        fn take(&self) -> String {
+            String::from_utf8(std::mem::take(&mut self.0.borrow_mut())).unwrap()
+        }
+    }
+
+    fn output(formatter: Formatter) -> (Output, Buffer) {
+        let buffer = Buffer::default();
+        (Output { path: None, io: Box::new(buffer.clone()), formatter }, buffer)
+    }
+
+    fn sample(output: &mut Output, sample: Option<Sample>) {
+        output.sample(Vec::new, || (), || sample).unwrap();
+    }
+
+    fn fan_speed(device_id: Option<u8>, timestamp: Option<jiff::Timestamp>) -> Option<Sample> {
+        let value = Number::Integer(1500);
+        Some(Sample { name: "SENSOR_FAN_SPEED", address: 12401, value, device_id, timestamp })
+    }
+
+    #[test]
+    fn prometheus_lines() {
+        let (mut output, buffer) = output(Formatter::Prometheus { seen: BTreeSet::new() });
+        sample(&mut output, fan_speed(Some(1), None));
+        sample(&mut output, None);
+        sample(&mut output, fan_speed(None, None));
+        assert_eq!(
+            buffer.take(),
+            "# TYPE sensor_fan_speed gauge\nsensor_fan_speed{address=\"12401\",device_id=\"1\"} 1500\n"
+        );
+        // Duplicate samples are only left out until the next flush.
+        output.flush().unwrap();
+        sample(&mut output, fan_speed(None, None));
+        assert_eq!(
+            buffer.take(),
+            "# TYPE sensor_fan_speed gauge\nsensor_fan_speed{address=\"12401\"} 1500\n"
+        );
+    }
+
+    #[test]
+    fn influx_lines() {
+        let (mut output, buffer) = output(Formatter::Influx);
+        let timestamp = jiff::Timestamp::from_second(1_700_000_000).unwrap();
+        sample(&mut output, fan_speed(Some(1), Some(timestamp)));
+        sample(&mut output, None);
+        sample(&mut output, fan_speed(None, None));
+        assert_eq!(
+            buffer.take(),
+            "systemair,address=12401,device_id=1 SENSOR_FAN_SPEED=1500 1700000000000000000\n\
+             systemair,address=12401 SENSOR_FAN_SPEED=1500\n"
+        );
+    }
+}
@@ -178,6 +178,11 @@ impl Mode {
 pub struct RegisterIndex(u16);
 
 impl RegisterIndex {
+    /// All of the known registers, ordered by their address.
+    pub fn all() -> impl Iterator<Item = RegisterIndex> {
+        (0..NAMES.len() as u16).map(RegisterIndex)
+    }
+
     pub const fn from_address(address: u16) -> Option<RegisterIndex> {
         let address = address as usize;
         #[expect(clippy::if_same_then_else)]
@@ -239,8 +244,43 @@ impl RegisterIndex {
     pub const fn maximum_value(&self) -> Option<Value> {
         MAXIMUM_VALUES[self.0 as usize]
     }
+
+    pub const fn description(&self) -> &'static str {
+        DESCRIPTIONS[self.0 as usize]
+    }
+
+    /// Whether writing to this register triggers a one-off action (such as setting the clock)
+    /// rather than changing a setting.
+    pub const fn is_action(&self) -> bool {
+        ACTIONS[self.0 as usize]
+    }
+
+    /// The effect of writing to this register, if it is safety-critical.
+    ///
+    /// Such writes can leave the device unreachable, wipe its configuration or bypass its
+    /// protective functions.
+    pub const fn critical_effect(&self) -> Option<&'static str> {
+        CRITICAL_EFFECTS[self.0 as usize]
+    }
 }
 
+// Effects of writing to the registers marked as `critical` in the table below.
+const EFFECT_FACTORY_RESET: &str =
+    "resets all settings to the factory defaults, including the Modbus communication settings";
+const EFFECT_ACTIVATE_USER_SAFE_CONFIG: &str =
+    "reverts all settings to the stored user safe config";
+const EFFECT_MODBUS_ADDRESS: &str =
+    "changes the Modbus address, after which the device stops responding to the current device ID";
+const EFFECT_MODBUS_LINK: &str = "changes the Modbus serial line settings, after which the device \
+    stops responding until the gateway is reconfigured to match";
+const EFFECT_INPUT_OVERRIDE: &str = "makes the device act on a fixed input value instead of the \
+    actual reading, until the override is set back to AUTO";
+const EFFECT_OUTPUT_OVERRIDE: &str = "runs the fan at a fixed speed regardless of the user mode, \
+    alarms and protective functions, until the override is set back to AUTO";
+const EFFECT_FIRE_ALARM_INPUT: &str = "reconfigures a digital input, which can disconnect the fire \
+    alarm or make it trigger spuriously";
+const EFFECT_FIRE_ALARM_CLEAR: &str = "clears the fire alarm and resumes normal operation";
+
 macro_rules! for_each_register {
     ($m:ident) => {
         $m! {
@@ -287,7 +327,7 @@ macro_rules! for_each_register {
             1148: U16, RW, "USERMODE_VACUUMCLEANER_AIRFLOW_LEVEL_EAF", min = 1, max = 5;
             1151: CEL, RW, "USERMODE_CROWDED_T_OFFSET", min = -100, max = 0;
             1161: U16, R_, "USERMODE_MODE", min = 0, max = 12;
-            1162: U16, RW, "USERMODE_HMI_CHANGE_REQUEST", min = 0, max = 7;
+            1162: U16, RW, "USERMODE_HMI_CHANGE_REQUEST", min = 0, max = 7, action = true;
             1171: U16, RW, "CDI_1_AIRFLOW_LEVEL_SAF", min = 0, max = 5;
             1172: U16, RW, "CDI_1_AIRFLOW_LEVEL_EAF", min = 0, max = 5;
             1173: U16, RW, "CDI_2_AIRFLOW_LEVEL_SAF", min = 0, max = 5;
@@ -535,12 +575,12 @@ macro_rules! for_each_register {
             5112: U16, RW, "WS_DAY6_PRD2_ENABLED", min = 0, max = 1;
             5113: U16, RW, "WS_DAY7_PRD1_ENABLED", min = 0, max = 1;
             5114: U16, RW, "WS_DAY7_PRD2_ENABLED", min = 0, max = 1;
-            6001: U16, RW, "TIME_YEAR", min = 0, max = 2999;
-            6002: U16, RW, "TIME_MONTH", min = 1, max = 12;
-            6003: U16, RW, "TIME_DAY", min = 1, max = 31;
-            6004: U16, RW, "TIME_HOUR", min = 0, max = 23;
-            6005: U16, RW, "TIME_MINUTE", min = 0, max = 59;
-            6006: U16, RW, "TIME_SECOND", min = 0, max = 59;
+            6001: U16, RW, "TIME_YEAR", min = 0, max = 2999, action = true;
+            6002: U16, RW, "TIME_MONTH", min = 1, max = 12, action = true;
+            6003: U16, RW, "TIME_DAY", min = 1, max = 31, action = true;
+            6004: U16, RW, "TIME_HOUR", min = 0, max = 23, action = true;
+            6005: U16, RW, "TIME_MINUTE", min = 0, max = 59, action = true;
+            6006: U16, RW, "TIME_SECOND", min = 0, max = 59, action = true;
             6007: U16, RW, "TIME_AUTO_SUM_WIN", min = 0, max = 1;
             6008: U16, RW, "HOUR_FORMAT", min = 0, max = 1;
             6009: U16, R_, "DAY_OF_THE_WEEK", min = 0, max = 6;
@@ -553,7 +593,7 @@ macro_rules! for_each_register {
             7001: U16, RW, "FILTER_PERIOD", min = 3, max = 15;
             7002: U16, RW, "FILTER_REPLACEMENT_TIME_L";
             7003: U16, RW, "FILTER_REPLACEMENT_TIME_H";
-            7004: U16, RW, "FILTER_PERIOD_SET";
+            7004: U16, RW, "FILTER_PERIOD_SET", action = true;
             7005: U16, R_, "FILTER_REMAINING_TIME_L";
             7006: U16, R_, "FILTER_REMAINING_TIME_H";
             7007: U16, R_, "FILTER_ALARM_WAS_DETECTED";
@@ -572,10 +612,14 @@ macro_rules! for_each_register {
             11204: U16, RW, "UI_4_FUNCTION";
             11205: U16, RW, "UI_5_FUNCTION";
             11206: U16, RW, "UI_6_FUNCTION";
-            11401: U16, RW, "DI_CONNECTION_1", min = 0, max = 18;
-            11402: U16, RW, "DI_CONNECTION_2", min = 0, max = 18;
-            11421: U16, RW, "DI_CFG_POLARITY_1", min = 0, max = 1;
-            11422: U16, RW, "DI_CFG_POLARITY_2", min = 0, max = 1;
+            11401: U16, RW, "DI_CONNECTION_1", min = 0, max = 18,
+                critical = EFFECT_FIRE_ALARM_INPUT;
+            11402: U16, RW, "DI_CONNECTION_2", min = 0, max = 18,
+                critical = EFFECT_FIRE_ALARM_INPUT;
+            11421: U16, RW, "DI_CFG_POLARITY_1", min = 0, max = 1,
+                critical = EFFECT_FIRE_ALARM_INPUT;
+            11422: U16, RW, "DI_CFG_POLARITY_2", min = 0, max = 1,
+                critical = EFFECT_FIRE_ALARM_INPUT;
             12011: U16, R_, "INPUT_ANALOG_UI_1";
             12012: U16, R_, "INPUT_ANALOG_UI_2";
             12013: U16, R_, "INPUT_ANALOG_UI_3";
@@ -638,52 +682,98 @@ macro_rules! for_each_register {
             12404: U16, R_, "SENSOR_FLOW_PIGGYBACK_EAF";
             12405: U16, R_, "SENSOR_DI_BYF";
             12544: CEL, RW, "SENSOR_PDM_EAT_VALUE", min = -400, max = 800;
-            12929: U16, RW, "MANUAL_OVERRIDE_F_INPUT_UI_SAFC_MODE", min = 0, max = 1;
-            12930: U16, RW, "MANUAL_OVERRIDE_F_INPUT_UI_EAFC_MODE", min = 0, max = 1;
-            12931: U16, RW, "MANUAL_OVERRIDE_F_INPUT_UI_RH_MODE", min = 0, max = 1;
-            12932: U16, RW, "MANUAL_OVERRIDE_F_INPUT_UI_CO2_MODE", min = 0, max = 1;
-            12933: U16, RW, "MANUAL_OVERRIDE_F_INPUT_OAT_MODE", min = 0, max = 1;
-            12934: U16, RW, "MANUAL_OVERRIDE_F_INPUT_SAT_MODE", min = 0, max = 1;
-            12935: U16, RW, "MANUAL_OVERRIDE_F_INPUT_OHT_MODE", min = 0, max = 1;
-            12936: U16, RW, "MANUAL_OVERRIDE_F_INPUT_FPT_MODE", min = 0, max = 1;
-            12937: U16, RW, "MANUAL_OVERRIDE_F_INPUT_RAT_MODE", min = 0, max = 1;
-            12938: U16, RW, "MANUAL_OVERRIDE_F_INPUT_EAT_MODE", min = 0, max = 1;
-            12939: U16, RW, "MANUAL_OVERRIDE_F_INPUT_ECT_MODE", min = 0, max = 1;
-            12940: U16, RW, "MANUAL_OVERRIDE_F_INPUT_EFT_MODE", min = 0, max = 1;
-            12941: U16, RW, "MANUAL_OVERRIDE_F_INPUT_PDM_RH_MODE", min = 0, max = 1;
-            12942: U16, RW, "MANUAL_OVERRIDE_F_INPUT_PDM_T_MODE", min = 0, max = 1;
-            12943: U16, RW, "MANUAL_OVERRIDE_INPUT_SAF_RPM_MODE", min = 0, max = 1;
-            12944: U16, RW, "MANUAL_OVERRIDE_INPUT_EAF_RPM_MODE", min = 0, max = 1;
-            12945: U16, RW, "MANUAL_OVERRIDE_INPUT_UI6_MODE", min = 0, max = 1;
-            12946: U16, RW, "MANUAL_OVERRIDE_INPUT_BYF_MODE", min = 0, max = 1;
-            12947: U16, RW, "MANUAL_OVERRIDE_INPUT_PIGGYBACK1_SAF_P_MODE", min = 0, max = 1;
-            12948: U16, RW, "MANUAL_OVERRIDE_INPUT_PIGGYBACK1_EAF_P_MODE", min = 0, max = 1;
-            12949: U16, RW, "MANUAL_OVERRIDE_INPUT_PIGGYBACK2_SAF_P_MODE", min = 0, max = 1;
-            12950: U16, RW, "MANUAL_OVERRIDE_INPUT_PIGGYBACK2_EAF_P_MODE", min = 0, max = 1;
-            12951: I16, RW, "MANUAL_OVERRIDE_INPUT_AI1_VALUE", min = -410, max = 810;
-            12952: I16, RW, "MANUAL_OVERRIDE_INPUT_AI2_VALUE", min = -410, max = 810;
-            12953: I16, RW, "MANUAL_OVERRIDE_INPUT_AI3_VALUE", min = -410, max = 810;
-            12954: I16, RW, "MANUAL_OVERRIDE_INPUT_AI4_VALUE", min = -410, max = 810;
-            12955: I16, RW, "MANUAL_OVERRIDE_INPUT_AI5_VALUE", min = -410, max = 810;
-            12956: I16, RW, "MANUAL_OVERRIDE_INPUT_AI6_VALUE", min = -410, max = 810;
-            12957: I16, RW, "MANUAL_OVERRIDE_INPUT_AI7_VALUE", min = -410, max = 810;
-            12958: I16, RW, "MANUAL_OVERRIDE_INPUT_DI1_VALUE", min = 0, max = 1;
-            12959: I16, RW, "MANUAL_OVERRIDE_INPUT_DI2_VALUE", min = 0, max = 1;
-            12960: I16, RW, "MANUAL_OVERRIDE_INPUT_UI1_VALUE", min = 0, max = 100;
-            12961: I16, RW, "MANUAL_OVERRIDE_INPUT_UI2_VALUE", min = 0, max = 100;
-            12962: I16, RW, "MANUAL_OVERRIDE_INPUT_UI3_VALUE", min = 0, max = 100;
-            12963: I16, RW, "MANUAL_OVERRIDE_INPUT_UI4_VALUE", min = 0, max = 100;
-            12964: I16, RW, "MANUAL_OVERRIDE_INPUT_UI5_VALUE", min = 0, max = 100;
-            12979: I16, RW, "MANUAL_OVERRIDE_F_INPUT_SAFC_VALUE", min = 0, max = 100;
-            12980: I16, RW, "MANUAL_OVERRIDE_F_INPUT_EAFC_VALUE", min = 0, max = 100;
-            12983: CEL, RW, "MANUAL_OVERRIDE_F_INPUT_OAT_VALUE", min = -410, max = 810;
-            12984: CEL, RW, "MANUAL_OVERRIDE_F_INPUT_SAT_VALUE", min = -410, max = 810;
-            12985: CEL, RW, "MANUAL_OVERRIDE_F_INPUT_OHT_VALUE", min = -410, max = 810;
-            12986: CEL, RW, "MANUAL_OVERRIDE_F_INPUT_FPT_VALUE", min = -410, max = 810;
-            12987: CEL, RW, "MANUAL_OVERRIDE_F_INPUT_RAT_VALUE", min = -410, max = 810;
-            12988: CEL, RW, "MANUAL_OVERRIDE_F_INPUT_EAT_VALUE", min = -410, max = 810;
-            12989: CEL, RW, "MANUAL_OVERRIDE_F_INPUT_ECT_VALUE", min = -410, max = 810;
-            12990: CEL, RW, "MANUAL_OVERRIDE_F_INPUT_EFT_VALUE", min = -410, max = 810;
+            12929: U16, RW, "MANUAL_OVERRIDE_F_INPUT_UI_SAFC_MODE", min = 0, max = 1,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12930: U16, RW, "MANUAL_OVERRIDE_F_INPUT_UI_EAFC_MODE", min = 0, max = 1,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12931: U16, RW, "MANUAL_OVERRIDE_F_INPUT_UI_RH_MODE", min = 0, max = 1,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12932: U16, RW, "MANUAL_OVERRIDE_F_INPUT_UI_CO2_MODE", min = 0, max = 1,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12933: U16, RW, "MANUAL_OVERRIDE_F_INPUT_OAT_MODE", min = 0, max = 1,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12934: U16, RW, "MANUAL_OVERRIDE_F_INPUT_SAT_MODE", min = 0, max = 1,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12935: U16, RW, "MANUAL_OVERRIDE_F_INPUT_OHT_MODE", min = 0, max = 1,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12936: U16, RW, "MANUAL_OVERRIDE_F_INPUT_FPT_MODE", min = 0, max = 1,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12937: U16, RW, "MANUAL_OVERRIDE_F_INPUT_RAT_MODE", min = 0, max = 1,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12938: U16, RW, "MANUAL_OVERRIDE_F_INPUT_EAT_MODE", min = 0, max = 1,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12939: U16, RW, "MANUAL_OVERRIDE_F_INPUT_ECT_MODE", min = 0, max = 1,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12940: U16, RW, "MANUAL_OVERRIDE_F_INPUT_EFT_MODE", min = 0, max = 1,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12941: U16, RW, "MANUAL_OVERRIDE_F_INPUT_PDM_RH_MODE", min = 0, max = 1,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12942: U16, RW, "MANUAL_OVERRIDE_F_INPUT_PDM_T_MODE", min = 0, max = 1,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12943: U16, RW, "MANUAL_OVERRIDE_INPUT_SAF_RPM_MODE", min = 0, max = 1,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12944: U16, RW, "MANUAL_OVERRIDE_INPUT_EAF_RPM_MODE", min = 0, max = 1,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12945: U16, RW, "MANUAL_OVERRIDE_INPUT_UI6_MODE", min = 0, max = 1,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12946: U16, RW, "MANUAL_OVERRIDE_INPUT_BYF_MODE", min = 0, max = 1,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12947: U16, RW, "MANUAL_OVERRIDE_INPUT_PIGGYBACK1_SAF_P_MODE", min = 0, max = 1,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12948: U16, RW, "MANUAL_OVERRIDE_INPUT_PIGGYBACK1_EAF_P_MODE", min = 0, max = 1,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12949: U16, RW, "MANUAL_OVERRIDE_INPUT_PIGGYBACK2_SAF_P_MODE", min = 0, max = 1,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12950: U16, RW, "MANUAL_OVERRIDE_INPUT_PIGGYBACK2_EAF_P_MODE", min = 0, max = 1,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12951: I16, RW, "MANUAL_OVERRIDE_INPUT_AI1_VALUE", min = -410, max = 810,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12952: I16, RW, "MANUAL_OVERRIDE_INPUT_AI2_VALUE", min = -410, max = 810,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12953: I16, RW, "MANUAL_OVERRIDE_INPUT_AI3_VALUE", min = -410, max = 810,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12954: I16, RW, "MANUAL_OVERRIDE_INPUT_AI4_VALUE", min = -410, max = 810,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12955: I16, RW, "MANUAL_OVERRIDE_INPUT_AI5_VALUE", min = -410, max = 810,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12956: I16, RW, "MANUAL_OVERRIDE_INPUT_AI6_VALUE", min = -410, max = 810,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12957: I16, RW, "MANUAL_OVERRIDE_INPUT_AI7_VALUE", min = -410, max = 810,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12958: I16, RW, "MANUAL_OVERRIDE_INPUT_DI1_VALUE", min = 0, max = 1,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12959: I16, RW, "MANUAL_OVERRIDE_INPUT_DI2_VALUE", min = 0, max = 1,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12960: I16, RW, "MANUAL_OVERRIDE_INPUT_UI1_VALUE", min = 0, max = 100,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12961: I16, RW, "MANUAL_OVERRIDE_INPUT_UI2_VALUE", min = 0, max = 100,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12962: I16, RW, "MANUAL_OVERRIDE_INPUT_UI3_VALUE", min = 0, max = 100,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12963: I16, RW, "MANUAL_OVERRIDE_INPUT_UI4_VALUE", min = 0, max = 100,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12964: I16, RW, "MANUAL_OVERRIDE_INPUT_UI5_VALUE", min = 0, max = 100,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12979: I16, RW, "MANUAL_OVERRIDE_F_INPUT_SAFC_VALUE", min = 0, max = 100,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12980: I16, RW, "MANUAL_OVERRIDE_F_INPUT_EAFC_VALUE", min = 0, max = 100,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12983: CEL, RW, "MANUAL_OVERRIDE_F_INPUT_OAT_VALUE", min = -410, max = 810,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12984: CEL, RW, "MANUAL_OVERRIDE_F_INPUT_SAT_VALUE", min = -410, max = 810,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12985: CEL, RW, "MANUAL_OVERRIDE_F_INPUT_OHT_VALUE", min = -410, max = 810,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12986: CEL, RW, "MANUAL_OVERRIDE_F_INPUT_FPT_VALUE", min = -410, max = 810,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12987: CEL, RW, "MANUAL_OVERRIDE_F_INPUT_RAT_VALUE", min = -410, max = 810,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12988: CEL, RW, "MANUAL_OVERRIDE_F_INPUT_EAT_VALUE", min = -410, max = 810,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12989: CEL, RW, "MANUAL_OVERRIDE_F_INPUT_ECT_VALUE", min = -410, max = 810,
+                critical = EFFECT_INPUT_OVERRIDE;
+            12990: CEL, RW, "MANUAL_OVERRIDE_F_INPUT_EFT_VALUE", min = -410, max = 810,
+                critical = EFFECT_INPUT_OVERRIDE;
             13201: U16, RW, "OUTPUT_TRIAC_CONFIGURED", min = 0, max = 1;
             13301: U16, R_, "DO1_AFTER_MUX", min = 0, max = 1;
             13302: U16, R_, "DO2_AFTER_MUX", min = 0, max = 1;
@@ -694,10 +784,14 @@ macro_rules! for_each_register {
             13313: U16, R_, "AO3_AFTER_MUX", min = 0, max = 100;
             13314: U16, R_, "AO4_AFTER_MUX", min = 0, max = 100;
             13315: U16, R_, "AO5_AFTER_MUX", min = 0, max = 100;
-            13601: U16, RW, "MANUAL_OVERRIDE_OUTPUT_SAF", min = 0, max = 1;
-            13602: U16, RW, "MANUAL_OVERRIDE_OUTPUT_EAF", min = 0, max = 1;
-            13801: U16, RW, "MANUAL_OVERRIDE_OUTPUT_SAF_VALUE", min = 0, max = 100;
-            13802: U16, RW, "MANUAL_OVERRIDE_OUTPUT_EAF_VALUE", min = 0, max = 100;
+            13601: U16, RW, "MANUAL_OVERRIDE_OUTPUT_SAF", min = 0, max = 1,
+                critical = EFFECT_OUTPUT_OVERRIDE;
+            13602: U16, RW, "MANUAL_OVERRIDE_OUTPUT_EAF", min = 0, max = 1,
+                critical = EFFECT_OUTPUT_OVERRIDE;
+            13801: U16, RW, "MANUAL_OVERRIDE_OUTPUT_SAF_VALUE", min = 0, max = 100,
+                critical = EFFECT_OUTPUT_OVERRIDE;
+            13802: U16, RW, "MANUAL_OVERRIDE_OUTPUT_EAF_VALUE", min = 0, max = 100,
+                critical = EFFECT_OUTPUT_OVERRIDE;
             14001: U16, R_, "OUTPUT_SAF", min = 0, max = 100;
             14002: U16, R_, "OUTPUT_EAF", min = 0, max = 100;
             14003: U16, R_, "OUTPUT_ALARM", min = 0, max = 1;
@@ -787,7 +881,8 @@ macro_rules! for_each_register {
             15530: U16, R_, "ALARM_OVERHEAT_TEMPERATURE_ALARM", min = 0, max = 3;
             15531: U16, RW, "ALARM_OVERHEAT_TEMPERATURE_CLEAR_ALARM", min = 0, max = 1;
             15537: U16, R_, "ALARM_FIRE_ALARM_ALARM", min = 0, max = 3;
-            15538: U16, RW, "ALARM_FIRE_ALARM_CLEAR_ALARM", min = 0, max = 1;
+            15538: U16, RW, "ALARM_FIRE_ALARM_CLEAR_ALARM", min = 0, max = 1,
+                critical = EFFECT_FIRE_ALARM_CLEAR;
             15544: U16, R_, "ALARM_FILTER_WARNING_ALARM", min = 0, max = 3;
             15545: U16, RW, "ALARM_FILTER_WARNING_CLEAR_ALARM", min = 0, max = 1;
             15549: U16, R_, "ALARM_FILTER_WARNING_ALARM_ERROR_DURATION_COUNTER";
@@ -1004,12 +1099,16 @@ macro_rules! for_each_register {
             16061: U16, RW, "PASSWD_PC_SETTINGS";
             16062: U16, R_, "PASSWD_PC_UNLOCKED", min = 0, max = 1;
             16101: U16, RW, "SUW_REQUIRED", min = 0, max = 1;
-            17001: U16, RW, "COMM_MODBUS_ADDRESS", min = 0, max = 255;
-            17002: U16, RW, "COMM_MODBUS_BAUD_RATE", min = 0, max = 10;
-            17003: U16, RW, "COMM_MODBUS_PARITY", min = 0, max = 2;
-            30101: U16, RW, "FACTORY_RESET", min = 3228, max = 3228;
-            30103: U16, RW, "SET_USER_SAFE_CONFIG", min = 0, max = 1;
-            30104: U16, RW, "ACTIVATE_USER_SAFE_CONFIG", min = 0, max = 1;
+            17001: U16, RW, "COMM_MODBUS_ADDRESS", min = 0, max = 255,
+                critical = EFFECT_MODBUS_ADDRESS;
+            17002: U16, RW, "COMM_MODBUS_BAUD_RATE", min = 0, max = 10,
+                critical = EFFECT_MODBUS_LINK;
+            17003: U16, RW, "COMM_MODBUS_PARITY", min = 0, max = 2, critical = EFFECT_MODBUS_LINK;
+            30101: U16, RW, "FACTORY_RESET", min = 3228, max = 3228, action = true,
+                critical = EFFECT_FACTORY_RESET;
+            30103: U16, RW, "SET_USER_SAFE_CONFIG", min = 0, max = 1, action = true;
+            30104: U16, RW, "ACTIVATE_USER_SAFE_CONFIG", min = 0, max = 1, action = true,
+                critical = EFFECT_ACTIVATE_USER_SAFE_CONFIG;
             30105: U16, R_, "USER_SAFE_CONFIG_VALID";
             30106: U16, R_, "SAFE_CONFIG_VALID";
         }
@@ -1026,13 +1125,15 @@ macro_rules! optional {
 }
 
 macro_rules! make_lists {
-    ($($regnum: literal: $dt: ident, $mode: ident, $name: literal $(, min = $min: literal)? $(, max = $max: literal)?;)+) => {
+    ($($regnum: literal: $dt: ident, $mode: ident, $name: literal $(, min = $min: literal)? $(, max = $max: literal)? $(, action = $action: literal)? $(, critical = $critical: expr)?;)+) => {
         pub static ADDRESSES: &[u16] = &[$($regnum),*];
         pub static NAMES: &[&str] = &[$($name),*];
         pub static MODES: &[Mode] = &[$(Mode::$mode),*];
         pub static DATA_TYPES: &[DataType] = &[$(DataType::$dt),*];
         pub static MINIMUM_VALUES: &[Option<Value>] = &[$(optional!($(Value::$dt($min))?)),*];
         pub static MAXIMUM_VALUES: &[Option<Value>] = &[$(optional!($(Value::$dt($max))?)),*];
+        pub static ACTIONS: &[bool] = &[$(false $(|| $action)?),*];
+        pub static CRITICAL_EFFECTS: &[Option<&str>] = &[$(optional!($($critical)?)),*];
     };
 }
 
@@ -1043,7 +1144,7 @@ pub static ADDRESS_INDICES: &[u16; 30107] = &const {
     let mut array = [0xFFFF; 30107];
     let mut index = 0;
     macro_rules! make_indices {
-        ($($regnum: literal: $dt: ident, $mode: ident, $name: literal $(, min = $min: literal)? $(, max = $max: literal)?;)+) => {
+        ($($regnum: literal: $dt: ident, $mode: ident, $name: literal $(, min = $min: literal)? $(, max = $max: literal)? $(, action = $action: literal)? $(, critical = $critical: expr)?;)+) => {
             $(array[$regnum] = index; index = index + 1;)+
         }
     }
@@ -0,0 +1,181 @@
+//! Saved listings of register values.
+//!
+//! Snapshots are stored as either JSON or TOML documents. The register name is the primary key of
+//! each entry, while the address is kept around for the benefit of humans and as a sanity check.
+
+use crate::registers::{ParseValueError, RegisterIndex, Value};
+use std::path::{Path, PathBuf};
+
+/// The snapshot format version produced by this version of the tool.
+pub const VERSION: u32 = 1;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum Format {
+    Json,
+    Toml,
+}
+
+impl Format {
+    /// Pick the format based on the file extension, defaulting to JSON.
+    pub fn from_path(path: Option<&Path>) -> Self {
+        match path.and_then(|p| p.extension()).and_then(|e| e.to_str()) {
+            Some("toml") => Format::Toml,
+            _ => Format::Json,
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("could not read {1:?}")]
+    Read(#[source] std::io::Error, PathBuf),
+    #[error("could not write to {1:?}")]
+    Write(#[source] std::io::Error, PathBuf),
+    #[error("could not write to the terminal")]
+    WriteStdout(#[source] std::io::Error),
+    #[error("could not parse {1:?} as JSON")]
+    ParseJson(#[source] serde_json::Error, PathBuf),
+    #[error("could not parse {1:?} as TOML")]
+    ParseToml(#[source] toml::de::Error, PathBuf),
+    #[error("could not serialize to JSON")]
+    SerializeJson(#[source] serde_json::Error),
+    #[error("could not serialize to TOML")]
+    SerializeToml(#[source] toml::ser::Error),
+    #[error("snapshot version {0} is not supported (expected at most {VERSION})")]
+    UnsupportedVersion(u32),
+    #[error("register `{0}` does not match any known register")]
+    RegisterNotFound(String),
+    #[error("register `{0}` is at address {1}, but the snapshot says {2}")]
+    AddressMismatch(&'static str, u16, u16),
+    #[error("could not parse value {1} for register {0}")]
+    ParseValue(&'static str, Number, #[source] ParseValueError),
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_version: Option<String>,
+    pub registers: Vec<Entry>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Entry {
+    pub name: String,
+    pub address: u16,
+    pub value: Number,
+}
+
+/// A register value as it appears in a snapshot.
+///
+/// Scaled values are stored as floating point numbers in the unit they represent, the same way
+/// they are displayed everywhere else.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Number {
+    Integer(i64),
+    Float(f64),
+}
+
+impl From<Value> for Number {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::U16(v) => Number::Integer(v.into()),
+            Value::I16(v) => Number::Integer(v.into()),
+            // Going through the string representation avoids the f32 -> f64 conversion artifacts.
+            Value::Celsius(_) | Value::SpecificHumidity(_) => {
+                Number::Float(value.to_string().parse().expect("floats format as floats"))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Number::Integer(v) => v.fmt(f),
+            Number::Float(v) => v.fmt(f),
+        }
+    }
+}
+
+impl Snapshot {
+    pub fn new(registers: Vec<Entry>) -> Self {
+        Self {
+            version: VERSION,
+            created: Some(jiff::Timestamp::now().to_string()),
+            tool_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            registers,
+        }
+    }
+
+    pub fn load(path: &Path, format: Format) -> Result<Self, Error> {
+        let snapshot: Self = load(path, format)?;
+        if snapshot.version > VERSION {
+            return Err(Error::UnsupportedVersion(snapshot.version));
+        }
+        Ok(snapshot)
+    }
+
+    /// Write the snapshot out to the specified file, or the terminal if no file is given.
+    pub fn save(&self, path: Option<&Path>, format: Format) -> Result<(), Error> {
+        save(self, path, format)
+    }
+}
+
+/// Read a document in the specified format, such as a snapshot.
+pub fn load<T: serde::de::DeserializeOwned>(path: &Path, format: Format) -> Result<T, Error> {
+    let data = std::fs::read_to_string(path).map_err(|e| Error::Read(e, path.into()))?;
+    match format {
+        Format::Json => serde_json::from_str(&data).map_err(|e| Error::ParseJson(e, path.into())),
+        Format::Toml => toml::from_str(&data).map_err(|e| Error::ParseToml(e, path.into())),
+    }
+}
+
+/// Write a document out to the specified file, or the terminal if no file is given.
+pub fn save<T: serde::Serialize>(
+    value: &T,
+    path: Option<&Path>,
+    format: Format,
+) -> Result<(), Error> {
+    let mut data = match format {
+        Format::Json => serde_json::to_string_pretty(value).map_err(Error::SerializeJson)?,
+        Format::Toml => toml::to_string_pretty(value).map_err(Error::SerializeToml)?,
+    };
+    if !data.ends_with('\n') {
+        data.push('\n');
+    }
+    match path {
+        None => {
+            use std::io::Write as _;
+            std::io::stdout().lock().write_all(data.as_bytes()).map_err(Error::WriteStdout)
+        }
+        Some(path) => std::fs::write(path, data).map_err(|e| Error::Write(e, path.into())),
+    }
+}
+
+impl Entry {
+    pub fn new(register: RegisterIndex, value: Value) -> Self {
+        Self { name: register.name().to_string(), address: register.address(), value: value.into() }
+    }
+
+    /// Find the register this entry refers to.
+    pub fn register(&self) -> Result<RegisterIndex, Error> {
+        let register = RegisterIndex::from_name(&self.name)
+            .ok_or_else(|| Error::RegisterNotFound(self.name.clone()))?;
+        if register.address() != self.address {
+            return Err(Error::AddressMismatch(register.name(), register.address(), self.address));
+        }
+        Ok(register)
+    }
+
+    /// Convert the stored value into the register's native representation.
+    pub fn parse_value(&self, register: RegisterIndex) -> Result<Value, Error> {
+        register
+            .data_type()
+            .parse_string(&self.value.to_string())
+            .map_err(|e| Error::ParseValue(register.name(), self.value, e))
+    }
+}